@@ -9,25 +9,32 @@ fn sleep() {
     ::std::thread::sleep(::std::time::Duration::from_millis(2000));
 }
 
-fn clear<T: Write + Send>(term: &mut Terminal<T>) -> Result<()> {
-    let dims = term.dimensions()?;
-    for _ in 0..dims.rows {
-        writeln!(term)?;
+fn cls<T: Write + Send>(term: &mut Terminal<T>) -> Result<()> {
+    if term.has_capability(&Capability::ClearScreen) {
+        term.clear_screen()?;
+    } else {
+        // Clear it a line at a time instead
+        for row in 0..term.dimensions()?.rows {
+            term.set_position(Position { row, column: 0 })?;
+            term.clear_line()?;
+        }
     }
+    term.flush()?;
     Ok(())
 }
 
-fn cls<T: Write + Send>(term: &mut Terminal<T>) -> Result<()> {
-    let dims = term.dimensions()?;
-    for row in 0..dims.rows {
-        term.set_position(Position { row, column: 0 })?;
-        term.delete_line()?;
+fn draw_frame<T: Write + Send>(term: &mut Terminal<T>, text: &str) -> Result<()> {
+    // Hold back drawing until the whole frame has been written, so it doesn't tear
+    {
+        let mut term = term.synchronized_update()?;
+        draw(&mut term, text)?;
     }
     term.flush()?;
+    sleep();
     Ok(())
 }
 
-fn draw_frame<T: Write + Send>(term: &mut Terminal<T>, text: &str) -> Result<()> {
+fn draw<T: Write + Send>(term: &mut Terminal<T>, text: &str) -> Result<()> {
     cls(term)?;
     let dims = term.dimensions()?;
     let fg = term.foreground_color()?;
@@ -45,10 +52,19 @@ fn draw_frame<T: Write + Send>(term: &mut Terminal<T>, text: &str) -> Result<()>
         term.set_position(Position { row, column: dims.columns - 1 })?;
         write!(term, " ")?;
     }
+    // Writing the bottom right cell scrolls the screen on some terminals, unless autowrap is
+    // turned off first
+    let nowrap = term.has_capability(&Capability::Autowrap);
+    if nowrap {
+        term.set_autowrap(false)?;
+    }
     term.set_position(Position { row: dims.rows - 1, column: 0 })?;
     for _ in 0..dims.columns {
         write!(term, " ")?;
     }
+    if nowrap {
+        term.set_autowrap(true)?;
+    }
     // Draw text
     let start = (dims.columns - text.len() as c_ushort) / 2;
     term.set_foreground_color(fg)?;
@@ -56,8 +72,6 @@ fn draw_frame<T: Write + Send>(term: &mut Terminal<T>, text: &str) -> Result<()>
     term.set_position(Position { row: dims.rows / 2, column: start })?;
     write!(term, "{}", text);
     term.set_position(Position { row: dims.rows - 1, column: 0 })?;
-    term.flush();
-    sleep();
     Ok(())
 }
 
@@ -65,8 +79,9 @@ fn run() -> Result<()> {
     let mut term = stdout()?;
     // We can test for the capabilities we know we will need up front, and change how we run the app
     // depending on what the console supports. In this case, we just bail if we don't have what we 
-    // need
-    let caps = &[Capability::Position, Capability::Dimensions];
+    // need. `set_position` falls back to relative motion if the terminal can't position the cursor
+    // directly, so we only need one of them.
+    let caps = &[Capability::Dimensions];
     if ! term.has_capabilities(caps) {
         let mut missing_caps = Vec::new();
         for cap in caps {
@@ -76,17 +91,35 @@ fn run() -> Result<()> {
         }
         bail!("cannot run example, terminal doesn't have required capabilities: {:?}", missing_caps)
     }
+    let clearing = &[Capability::ClearScreen, Capability::ClearLine];
+    if ! clearing.iter().any(|cap| term.has_capability(cap)) {
+        bail!("cannot run example, terminal can't clear the screen")
+    }
+    let positioning = &[Capability::Position, Capability::AxisPosition, Capability::RelativeMotion];
+    if ! positioning.iter().any(|cap| term.has_capability(cap)) {
+        bail!("cannot run example, terminal can't move the cursor")
+    }
+
+    // Draw on the alternate screen if there is one, so the frames don't end up in the
+    // scrollback. The normal screen comes back when the guard is dropped.
+    if term.has_capability(&Capability::AlternateScreen) {
+        let mut screen = term.enter_alternate_screen()?;
+        show(&mut screen)
+    } else {
+        show(&mut term)
+    }
+}
 
-    clear(&mut term)?;
-    draw_frame(&mut term, "Ignore the following it's not real")?;
-    draw_frame(&mut term, "Hi")?;
-    draw_frame(&mut term, "")?;
-    draw_frame(&mut term, "We've updated your computer")?;
-    draw_frame(&mut term, "")?;
-    draw_frame(&mut term, "All your files are right where you've left them")?;
-    draw_frame(&mut term, "")?;
-    draw_frame(&mut term, "We have some new features that we are excited about")?;
-    draw_frame(&mut term, "")?;
+fn show<T: Write + Send>(term: &mut Terminal<T>) -> Result<()> {
+    draw_frame(term, "Ignore the following it's not real")?;
+    draw_frame(term, "Hi")?;
+    draw_frame(term, "")?;
+    draw_frame(term, "We've updated your computer")?;
+    draw_frame(term, "")?;
+    draw_frame(term, "All your files are right where you've left them")?;
+    draw_frame(term, "")?;
+    draw_frame(term, "We have some new features that we are excited about")?;
+    draw_frame(term, "")?;
     writeln!(term)?;
     term.reset()
 }
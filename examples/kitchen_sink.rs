@@ -10,19 +10,25 @@ use std::time;
 
 fn spin<T: Write + Send>(t: &mut Terminal<T>) -> Result<()> {
     let tm = time::Duration::from_millis(100);
+    // the cursor is shown again when `t` goes out of scope
+    let mut t = t.hidden_cursor()?;
     for _ in 0..5 {
         write!(t, "/")?;
+        t.flush()?;
         thread::sleep(tm);
-        t.carriage_return();
+        t.carriage_return()?;
         write!(t, "|")?;
+        t.flush()?;
         thread::sleep(tm);
-        t.carriage_return();
+        t.carriage_return()?;
         write!(t, "\\")?;
+        t.flush()?;
         thread::sleep(tm);
-        t.carriage_return();
+        t.carriage_return()?;
         write!(t, "-")?;
+        t.flush()?;
         thread::sleep(tm);
-        t.carriage_return();
+        t.carriage_return()?;
     }
     writeln!(t)?;
     Ok(())
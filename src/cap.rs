@@ -1,13 +1,25 @@
 //! The color/terminal capability enums and associated helper methods
 
+use std::env;
 use std::fmt;
 
 /// All available capabilities, can be filtered to get a list of supported capabilities
 pub const CAPABILITIES: &'static [Capability] = 
     &[Capability::Bold, Capability::Dim, Capability::Italic, Capability::Underline, 
       Capability::Blink, Capability::Standout, Capability::Reverse, Capability::Secure, 
-      Capability::ForegroundColor, Capability::BackgroundColor, Capability::Reset, 
-      Capability::Position, Capability::Dimensions];
+      Capability::Strikethrough, Capability::Overline, Capability::Conceal,
+      Capability::NormalIntensity, Capability::ForegroundColor, Capability::BackgroundColor, Capability::Reset, 
+      Capability::Position, Capability::RelativeMotion, Capability::AxisPosition,
+      Capability::TabStops, Capability::SaveCursor,
+      Capability::CursorVisibility, Capability::CursorShape, Capability::CursorColor,
+      Capability::ClearScreen, Capability::ClearLine, Capability::ClearScrollback,
+      Capability::AlternateScreen, Capability::ScrollRegion, Capability::Scroll,
+      Capability::InsertDeleteLines, Capability::InsertDeleteChars, Capability::EraseChars,
+      Capability::Autowrap, Capability::InsertMode,
+      Capability::Title, Capability::VisualBell, Capability::Notification,
+      Capability::SynchronizedOutput, Capability::Dimensions, Capability::PixelDimensions,
+      Capability::ResizeWindow,
+      Capability::Hyperlink];
 
 /// The primary colors used in a terminal.
 /// 
@@ -53,6 +65,78 @@ impl fmt::Display for Color {
     }
 }
 
+/// The shape of the cursor
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CursorShape {
+    /// Whatever shape the user has configured
+    Default,
+    BlinkingBlock,
+    SteadyBlock,
+    BlinkingUnderline,
+    SteadyUnderline,
+    BlinkingBar,
+    SteadyBar,
+}
+
+impl fmt::Display for CursorShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &CursorShape::Default => write!(f, "default"),
+            &CursorShape::BlinkingBlock => write!(f, "blinking block"),
+            &CursorShape::SteadyBlock => write!(f, "steady block"),
+            &CursorShape::BlinkingUnderline => write!(f, "blinking underline"),
+            &CursorShape::SteadyUnderline => write!(f, "steady underline"),
+            &CursorShape::BlinkingBar => write!(f, "blinking bar"),
+            &CursorShape::SteadyBar => write!(f, "steady bar"),
+        }
+    }
+}
+
+/// Whether to style text (with colors and other attributes)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ColorChoice {
+    /// Always style text, even when not writing to a terminal (e.g. when piping into `less -R`)
+    Always,
+    /// Style text when writing to a terminal, unless the `NO_COLOR` environment variable is set.
+    /// `CLICOLOR_FORCE` forces styling on, and `CLICOLOR=0` turns it off.
+    Auto,
+    /// Never style text. Setting attributes does nothing.
+    Never,
+}
+
+impl Default for ColorChoice {
+    fn default() -> Self {
+        ColorChoice::Auto
+    }
+}
+
+impl ColorChoice {
+    /// Whether text should be styled, given whether we are writing to a terminal
+    pub(crate) fn should_style(self, tty: bool) -> bool {
+        let var = |name| env::var(name).ok();
+        self.should_style_with(tty, var("NO_COLOR"), var("CLICOLOR_FORCE"), var("CLICOLOR"))
+    }
+
+    fn should_style_with(self, tty: bool, no_color: Option<String>,
+                         clicolor_force: Option<String>, clicolor: Option<String>) -> bool
+    {
+        let set = |v: &Option<String>| v.as_ref().map(|v| !v.is_empty()).unwrap_or(false);
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                if set(&clicolor_force) && clicolor_force.as_ref().unwrap() != "0" {
+                    true
+                } else if set(&no_color) || clicolor.as_ref().map(|v| v == "0").unwrap_or(false) {
+                    false
+                } else {
+                    tty
+                }
+            }
+        }
+    }
+}
+
 /// Potential terminal capabilities
 /// 
 /// These capabilities can be tested for, letting you know what a terminal is capable of on a
@@ -75,6 +159,14 @@ pub enum Capability {
     Reverse,
     /// Can accept secure text (not displayed on screen - for passwords)
     Secure,
+    /// Text can be struck through
+    Strikethrough,
+    /// Text can have a line drawn above it
+    Overline,
+    /// Text can be concealed (printed but not shown)
+    Conceal,
+    /// Bold and dim can be turned off without resetting other attributes
+    NormalIntensity,
     /// Can change the text color
     ForegroundColor,
     /// Can change the background color
@@ -83,8 +175,64 @@ pub enum Capability {
     Reset,
     /// Whether the terminal can give and set the cursor position
     Position,
+    /// Whether the cursor can be moved relative to where it is (up, down, left and right)
+    RelativeMotion,
+    /// Whether the cursor can be moved to a column or row without changing the other
+    AxisPosition,
+    /// Whether tab stops can be set and cleared, and the cursor moved back a tab stop
+    TabStops,
+    /// Whether the cursor position can be saved and restored
+    SaveCursor,
+    /// Whether the cursor can be hidden and shown
+    CursorVisibility,
+    /// Whether the shape of the cursor can be changed
+    CursorShape,
+    /// Whether the color of the cursor can be changed
+    CursorColor,
+    /// Whether the whole screen can be cleared. Clearing only the part before or after the
+    /// cursor may still be unsupported, in which case it gives `NotSupported`.
+    ClearScreen,
+    /// Whether the line, and the parts of it before and after the cursor, can all be cleared
+    ClearLine,
+    /// Whether the lines that have scrolled off the top of the screen can be cleared
+    ClearScrollback,
+    /// Whether there is a separate screen to draw on, which doesn't touch the normal screen or
+    /// its scrollback
+    AlternateScreen,
+    /// Whether scrolling can be limited to a range of rows
+    ScrollRegion,
+    /// Whether the screen can be scrolled without moving the cursor, and the cursor moved up
+    /// scrolling at the top (reverse index)
+    Scroll,
+    /// Whether lines can be inserted and deleted at the cursor, moving the lines below
+    InsertDeleteLines,
+    /// Whether characters can be inserted and deleted at the cursor, moving the rest of the line
+    InsertDeleteChars,
+    /// Whether characters can be erased from the cursor without moving anything
+    EraseChars,
+    /// Whether wrapping at the end of the line can be turned off
+    Autowrap,
+    /// Whether text can be inserted in front of what's already on the line instead of
+    /// replacing it
+    InsertMode,
+    /// Whether the title of the window (or tab) can be set
+    Title,
+    /// Whether the screen can be flashed instead of ringing the bell
+    VisualBell,
+    /// Whether the terminal can send desktop notifications
+    Notification,
+    /// Whether the terminal can hold back drawing until a whole update has been written (DEC
+    /// mode 2026). Unless the terminfo entry says so, this is only known once
+    /// `begin_synchronized_update` has asked the terminal.
+    SynchronizedOutput,
     /// Whether we can find out the screen dimensions
     Dimensions,
+    /// Whether we can find out the size of the window and its cells in pixels
+    PixelDimensions,
+    /// Whether we can ask for the window to be resized
+    ResizeWindow,
+    /// Text can be made into a clickable link
+    Hyperlink,
 }
 
 impl fmt::Display for Capability {
@@ -98,11 +246,61 @@ impl fmt::Display for Capability {
             &Capability::Standout => write!(f, "standout"),
             &Capability::Reverse => write!(f, "reverse"),
             &Capability::Secure => write!(f, "secure"),
+            &Capability::Strikethrough => write!(f, "strikethrough"),
+            &Capability::Overline => write!(f, "overline"),
+            &Capability::Conceal => write!(f, "conceal"),
+            &Capability::NormalIntensity => write!(f, "normal intensity"),
             &Capability::ForegroundColor => write!(f, "foreground color"),
             &Capability::BackgroundColor => write!(f, "background color"),
             &Capability::Reset => write!(f, "reset"),
             &Capability::Position => write!(f, "position"),
+            &Capability::RelativeMotion => write!(f, "relative motion"),
+            &Capability::AxisPosition => write!(f, "axis position"),
+            &Capability::TabStops => write!(f, "tab stops"),
+            &Capability::SaveCursor => write!(f, "save cursor"),
+            &Capability::CursorVisibility => write!(f, "cursor visibility"),
+            &Capability::CursorShape => write!(f, "cursor shape"),
+            &Capability::CursorColor => write!(f, "cursor color"),
+            &Capability::ClearScreen => write!(f, "clear screen"),
+            &Capability::ClearLine => write!(f, "clear line"),
+            &Capability::ClearScrollback => write!(f, "clear scrollback"),
+            &Capability::AlternateScreen => write!(f, "alternate screen"),
+            &Capability::ScrollRegion => write!(f, "scroll region"),
+            &Capability::Scroll => write!(f, "scroll"),
+            &Capability::InsertDeleteLines => write!(f, "insert and delete lines"),
+            &Capability::InsertDeleteChars => write!(f, "insert and delete characters"),
+            &Capability::EraseChars => write!(f, "erase characters"),
+            &Capability::Autowrap => write!(f, "autowrap"),
+            &Capability::InsertMode => write!(f, "insert mode"),
+            &Capability::Title => write!(f, "title"),
+            &Capability::VisualBell => write!(f, "visual bell"),
+            &Capability::Notification => write!(f, "notification"),
+            &Capability::SynchronizedOutput => write!(f, "synchronized output"),
             &Capability::Dimensions => write!(f, "dimensions"),
+            &Capability::PixelDimensions => write!(f, "pixel dimensions"),
+            &Capability::ResizeWindow => write!(f, "resize window"),
+            &Capability::Hyperlink => write!(f, "hyperlink"),
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::ColorChoice;
+
+    #[test]
+    fn color_choice() {
+        let s = |v: &str| Some(v.to_string());
+        assert!(ColorChoice::Always.should_style_with(false, s("1"), None, None));
+        assert!(!ColorChoice::Never.should_style_with(true, None, s("1"), None));
+        assert!(ColorChoice::Auto.should_style_with(true, None, None, None));
+        assert!(!ColorChoice::Auto.should_style_with(false, None, None, None));
+        assert!(!ColorChoice::Auto.should_style_with(true, s("1"), None, None));
+        // an empty NO_COLOR doesn't count
+        assert!(ColorChoice::Auto.should_style_with(true, s(""), None, None));
+        assert!(ColorChoice::Auto.should_style_with(false, None, s("1"), None));
+        assert!(!ColorChoice::Auto.should_style_with(false, None, s("0"), None));
+        assert!(!ColorChoice::Auto.should_style_with(true, None, None, s("0")));
+    }
+}
@@ -13,5 +13,9 @@ error_chain! {
             description("the terminal does not have the required capability"),
             display("the terminal does not have the \"{}\" capability", c)
         }
+        NoResponse {
+            description("the terminal did not respond to a query in time"),
+            display("the terminal did not respond to a query in time")
+        }
     }
 }
\ No newline at end of file
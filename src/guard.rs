@@ -0,0 +1,92 @@
+//! Guards that undo a change to the terminal when they go out of scope
+//!
+//! Each guard borrows the terminal mutably and dereferences to it, so it can be used in place of
+//! the terminal while the change is in effect. Errors while undoing the change are ignored, since
+//! there is nothing sensible to do with them during drop.
+
+use std::io;
+use std::ops::{Deref, DerefMut};
+
+use Terminal;
+
+/// Define a guard that calls a method on the terminal when it is dropped
+macro_rules! guard {
+    ($(#[$attr:meta])* pub struct $name:ident => $undo:ident;) => {
+        $(#[$attr])*
+        pub struct $name<'a, T: io::Write + Send + 'a> {
+            term: &'a mut Terminal<T>,
+        }
+
+        impl<'a, T: io::Write + Send> $name<'a, T> {
+            pub(crate) fn new(term: &'a mut Terminal<T>) -> Self {
+                $name { term }
+            }
+        }
+
+        impl<'a, T: io::Write + Send> Deref for $name<'a, T> {
+            type Target = Terminal<T>;
+
+            fn deref(&self) -> &Terminal<T> {
+                self.term
+            }
+        }
+
+        impl<'a, T: io::Write + Send> DerefMut for $name<'a, T> {
+            fn deref_mut(&mut self) -> &mut Terminal<T> {
+                self.term
+            }
+        }
+
+        impl<'a, T: io::Write + Send> Drop for $name<'a, T> {
+            fn drop(&mut self) {
+                let _ = self.term.$undo();
+            }
+        }
+    }
+}
+
+guard! {
+    /// Ends a hyperlink when dropped. Created by `Terminal::hyperlink`.
+    pub struct HyperlinkGuard => end_hyperlink;
+}
+
+guard! {
+    /// Moves the cursor back to where it was saved when dropped. Created by
+    /// `Terminal::saved_cursor`.
+    pub struct CursorGuard => restore_cursor;
+}
+
+guard! {
+    /// Shows the cursor again when dropped. Created by `Terminal::hidden_cursor`.
+    pub struct HiddenCursorGuard => show_cursor;
+}
+
+guard! {
+    /// Switches back from the alternate screen when dropped. Created by
+    /// `Terminal::enter_alternate_screen`.
+    pub struct AlternateScreenGuard => leave_alternate_screen;
+}
+
+guard! {
+    /// Puts back the title saved when it was created, when dropped. Created by
+    /// `Terminal::saved_title`.
+    pub struct TitleGuard => pop_title;
+}
+
+guard! {
+    /// Ends a synchronized update when dropped, so the terminal draws it. Created by
+    /// `Terminal::synchronized_update`.
+    pub struct SynchronizedUpdateGuard => end_synchronized_update;
+}
+
+guard! {
+    /// Puts the tty's input settings back how they were when dropped. Created by
+    /// `Terminal::enable_raw_mode` and `Terminal::enable_cbreak_mode`.
+    pub struct InputModeGuard => restore_input_mode;
+}
+
+guard! {
+    /// Resets the shape and color of the cursor when dropped. Created by
+    /// `Terminal::cursor_style`.
+    pub struct CursorStyleGuard => reset_cursor_style;
+}
@@ -0,0 +1,415 @@
+//! Decoding keyboard input into keys
+//!
+//! Terminals send keys as bytes: text as utf-8, control keys as control characters, and
+//! everything else as escape sequences, which differ between terminals. Sequences listed in
+//! the terminfo entry are recognised first, then the common xterm, vt220 and linux console ones.
+
+use std::str;
+use std::time::Duration;
+
+/// A key on the keyboard
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum KeyCode {
+    /// A key that types a character. With ctrl held this is the lowercase letter, e.g. ctrl-c
+    /// is `Char('c')` with `ctrl` set.
+    Char(char),
+    Enter,
+    Tab,
+    /// Shift-tab
+    BackTab,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// A function key, from 1 to 24
+    F(u8),
+}
+
+/// The modifier keys held down with a key
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+/// A key that was pressed, with the modifiers held down at the time
+///
+/// Terminals can only report some combinations, e.g. shift is never reported with letters
+/// (they are uppercase instead), and ctrl-i can't be told apart from tab.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    fn new(code: KeyCode) -> KeyEvent {
+        KeyEvent { code, modifiers: Modifiers::default() }
+    }
+
+    fn ctrl(code: KeyCode) -> KeyEvent {
+        KeyEvent { code, modifiers: Modifiers { ctrl: true, ..Modifiers::default() } }
+    }
+
+    fn with_alt(mut self) -> KeyEvent {
+        self.modifiers.alt = true;
+        self
+    }
+}
+
+/// The modifiers in an xterm style modifier parameter, which is 1 plus a bitmask
+fn modifiers(param: u32) -> Modifiers {
+    let bits = param.saturating_sub(1);
+    Modifiers {
+        shift: bits & 1 != 0,
+        // 8 is meta, which is usually the same key
+        alt: bits & (2 | 8) != 0,
+        ctrl: bits & 4 != 0,
+    }
+}
+
+/// The key for a vt220 style `CSI n ~` sequence
+fn tilde_key(n: u32) -> Option<KeyCode> {
+    Some(match n {
+        1 | 7 => KeyCode::Home,
+        2 => KeyCode::Insert,
+        3 => KeyCode::Delete,
+        4 | 8 => KeyCode::End,
+        5 => KeyCode::PageUp,
+        6 => KeyCode::PageDown,
+        11..=15 => KeyCode::F((n - 10) as u8),
+        17..=21 => KeyCode::F((n - 11) as u8),
+        23..=26 => KeyCode::F((n - 12) as u8),
+        28 | 29 => KeyCode::F((n - 13) as u8),
+        31..=34 => KeyCode::F((n - 14) as u8),
+        _ => return None,
+    })
+}
+
+/// The key for the final byte of a `CSI 1 ; modifiers X` or `SS3 X` sequence
+fn letter_key(byte: u8) -> Option<KeyCode> {
+    Some(match byte {
+        b'A' => KeyCode::Up,
+        b'B' => KeyCode::Down,
+        b'C' => KeyCode::Right,
+        b'D' => KeyCode::Left,
+        b'H' => KeyCode::Home,
+        b'F' => KeyCode::End,
+        b'Z' => KeyCode::BackTab,
+        b'P'..=b'S' => KeyCode::F(byte - b'P' + 1),
+        _ => return None,
+    })
+}
+
+/// The key for a codepoint in a `CSI codepoint ; modifiers u` sequence
+fn codepoint_key(codepoint: u32) -> Option<KeyCode> {
+    Some(match codepoint {
+        9 => KeyCode::Tab,
+        13 => KeyCode::Enter,
+        27 => KeyCode::Esc,
+        127 => KeyCode::Backspace,
+        _ => KeyCode::Char(::std::char::from_u32(codepoint)?),
+    })
+}
+
+/// The key for a single byte that isn't part of an escape sequence or utf-8 character
+fn control_key(byte: u8) -> KeyEvent {
+    match byte {
+        b'\r' | b'\n' => KeyEvent::new(KeyCode::Enter),
+        b'\t' => KeyEvent::new(KeyCode::Tab),
+        0x08 | 0x7f => KeyEvent::new(KeyCode::Backspace),
+        0x1b => KeyEvent::new(KeyCode::Esc),
+        0x00 => KeyEvent::ctrl(KeyCode::Char(' ')),
+        0x01..=0x1a => KeyEvent::ctrl(KeyCode::Char((byte - 1 + b'a') as char)),
+        // ctrl-\ ctrl-] ctrl-^ and ctrl-_
+        _ => KeyEvent::ctrl(KeyCode::Char((byte + 0x40) as char)),
+    }
+}
+
+/// Turns bytes into keys
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    /// The sequences from the terminfo entry, longest first
+    keys: Vec<(Vec<u8>, KeyCode)>,
+}
+
+impl Decoder {
+    pub fn new(mut keys: Vec<(Vec<u8>, KeyCode)>) -> Decoder {
+        keys.retain(|&(ref seq, _)| !seq.is_empty());
+        keys.sort_by(|&(ref a, _), &(ref b, _)| b.len().cmp(&a.len()));
+        Decoder { keys }
+    }
+
+    /// Decode the first key in `buf`, returning it and how many bytes it took up. The key is
+    /// `None` if the bytes were something we don't understand, which should be skipped.
+    ///
+    /// Returns `None` if `buf` might be the start of a longer sequence, unless `complete` says
+    /// no more input is coming, in which case it is decoded as whatever keys it can be.
+    pub fn decode(&self, buf: &[u8], complete: bool) -> Option<(Option<KeyEvent>, usize)> {
+        self.decode_key(buf, complete, true)
+    }
+
+    /// Decode the first key in `buf`, which may have an ESC in front of it for alt only if
+    /// `alt_prefix` is set
+    fn decode_key(&self, buf: &[u8], complete: bool, alt_prefix: bool)
+                  -> Option<(Option<KeyEvent>, usize)> {
+        let first = *buf.first()?;
+        // the terminfo entry knows best, and has sequences that can't be told apart otherwise,
+        // such as rxvt's `CSI 23 $`
+        let known = self.keys.iter().find(|&&(ref seq, _)| buf.starts_with(seq));
+        if let Some(&(ref seq, code)) = known {
+            return Some((Some(KeyEvent::new(code)), seq.len()));
+        }
+        if !complete && self.keys.iter().any(|&(ref seq, _)| seq.starts_with(buf)) {
+            return None;
+        }
+        if first == 0x1b {
+            return self.decode_escape(buf, complete, alt_prefix);
+        }
+        if first < 0x20 || first == 0x7f {
+            return Some((Some(control_key(first)), 1));
+        }
+        let len = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return Some((None, 1)),
+        };
+        if buf.len() < len {
+            return if complete { Some((None, 1)) } else { None };
+        }
+        match str::from_utf8(&buf[..len]) {
+            Ok(text) => Some((text.chars().next().map(|c| KeyEvent::new(KeyCode::Char(c))), len)),
+            Err(_) => Some((None, 1)),
+        }
+    }
+
+    /// Decode a key starting with ESC: a lone Esc, an escape sequence or alt with another key
+    fn decode_escape(&self, buf: &[u8], complete: bool, alt_prefix: bool)
+                     -> Option<(Option<KeyEvent>, usize)> {
+        let sequence = match buf.get(1) {
+            None if complete => return Some((Some(KeyEvent::new(KeyCode::Esc)), 1)),
+            None => return None,
+            Some(&b'[') => decode_csi(buf),
+            Some(&b'O') => decode_ss3(buf),
+            // the key after the prefix can't have one of its own, so ESC ESC is alt-Esc
+            Some(_) if alt_prefix => {
+                let (key, len) = self.decode_key(&buf[1..], complete, false)?;
+                return Some((key.map(KeyEvent::with_alt), len + 1));
+            }
+            Some(_) => return Some((Some(KeyEvent::new(KeyCode::Esc)), 1)),
+        };
+        match sequence {
+            Some(decoded) => Some(decoded),
+            // the sequence was cut short, so it must have been alt-[ or alt-O
+            None if complete => {
+                Some((Some(KeyEvent::new(KeyCode::Char(buf[1] as char)).with_alt()), 2))
+            }
+            None => None,
+        }
+    }
+}
+
+/// Decode a `CSI params final` sequence, or the linux console's `CSI [ letter` function keys
+fn decode_csi(buf: &[u8]) -> Option<(Option<KeyEvent>, usize)> {
+    if buf.get(2) == Some(&b'[') {
+        let key = match *buf.get(3)? {
+            byte @ b'A'..=b'E' => Some(KeyEvent::new(KeyCode::F(byte - b'A' + 1))),
+            _ => None,
+        };
+        return Some((key, 4));
+    }
+    let mut i = 2;
+    let mut private = false;
+    let mut params = Vec::new();
+    let mut param: Option<u32> = None;
+    let mut subparam = false;
+    let final_byte = loop {
+        match *buf.get(i)? {
+            b @ b'0'..=b'9' => if !subparam {
+                let digit = (b - b'0') as u32;
+                param = Some(param.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            },
+            b';' => {
+                params.push(param.take());
+                subparam = false;
+            }
+            // only the first part of a parameter like `97:65` matters here
+            b':' => subparam = true,
+            b'<'..=b'?' => private = true,
+            0x20..=0x2f => (),
+            b @ 0x40..=0x7e => break b,
+            // not a well formed sequence, skip what there was of it
+            _ => return Some((None, i)),
+        }
+        i += 1;
+    };
+    params.push(param);
+    let len = i + 1;
+    if private {
+        // mouse reports and the like
+        return Some((None, len));
+    }
+    let first = params[0].unwrap_or(1);
+    let code = match final_byte {
+        b'~' => tilde_key(first),
+        b'u' => codepoint_key(first),
+        _ => letter_key(final_byte),
+    };
+    let modifiers = params.get(1).and_then(|&m| m).map(modifiers).unwrap_or_default();
+    Some((code.map(|code| KeyEvent { code, modifiers }), len))
+}
+
+/// Decode an `SS3 final` sequence, which some terminals send with a modifier parameter as in
+/// `SS3 5 A`
+fn decode_ss3(buf: &[u8]) -> Option<(Option<KeyEvent>, usize)> {
+    let mut i = 2;
+    let mut param = None;
+    while let b @ b'0'..=b'9' = *buf.get(i)? {
+        param = Some(param.unwrap_or(0u32).saturating_mul(10).saturating_add((b - b'0') as u32));
+        i += 1;
+    }
+    let code = match buf[i] {
+        // the enter key on the keypad
+        b'M' => Some(KeyCode::Enter),
+        byte => letter_key(byte),
+    };
+    let modifiers = param.map(modifiers).unwrap_or_default();
+    Some((code.map(|code| KeyEvent { code, modifiers }), i + 1))
+}
+
+/// Input that has been read but not yet turned into keys
+#[derive(Debug, Clone)]
+pub struct Reader {
+    decoder: Decoder,
+    buf: Vec<u8>,
+    /// How long to wait for the rest of an escape sequence before deciding Esc was pressed
+    pub escape_timeout: Duration,
+}
+
+impl Reader {
+    pub fn new(keys: Vec<(Vec<u8>, KeyCode)>) -> Reader {
+        Reader {
+            decoder: Decoder::new(keys),
+            buf: Vec::new(),
+            escape_timeout: Duration::from_millis(100),
+        }
+    }
+
+    /// Add input that has been read
+    pub fn push(&mut self, input: &[u8]) {
+        self.buf.extend_from_slice(input);
+    }
+
+    /// Whether there is input left over that may be the start of an escape sequence
+    pub fn is_partial(&self) -> bool {
+        !self.buf.is_empty()
+    }
+
+    /// Take the next key out of the input, if there is a whole one. If `complete`, the input
+    /// isn't waiting for more to arrive.
+    pub fn next_key(&mut self, complete: bool) -> Option<KeyEvent> {
+        while let Some((key, len)) = self.decoder.decode(&self.buf, complete) {
+            self.buf.drain(..len);
+            if key.is_some() {
+                return key;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, KeyCode, KeyEvent, Modifiers, Reader};
+
+    fn keys(input: &[u8]) -> Vec<KeyEvent> {
+        let terminfo = vec![(b"\x1bOw".to_vec(), KeyCode::F(24)),
+                            (b"\x1b[23$".to_vec(), KeyCode::F(21))];
+        let mut reader = Reader::new(terminfo);
+        reader.push(input);
+        let mut keys = Vec::new();
+        while let Some(key) = reader.next_key(true) {
+            keys.push(key);
+        }
+        keys
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code)
+    }
+
+    fn modified(code: KeyCode, shift: bool, alt: bool, ctrl: bool) -> KeyEvent {
+        KeyEvent { code, modifiers: Modifiers { shift, alt, ctrl } }
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(keys("a\u{e9}\u{4e16}".as_bytes()),
+                   vec![key(KeyCode::Char('a')), key(KeyCode::Char('\u{e9}')),
+                        key(KeyCode::Char('\u{4e16}'))]);
+        // invalid utf-8 is skipped
+        assert_eq!(keys(b"\xffa"), vec![key(KeyCode::Char('a'))]);
+    }
+
+    #[test]
+    fn control() {
+        assert_eq!(keys(b"\r\t\x7f\x03\x00\x1d"),
+                   vec![key(KeyCode::Enter), key(KeyCode::Tab), key(KeyCode::Backspace),
+                        modified(KeyCode::Char('c'), false, false, true),
+                        modified(KeyCode::Char(' '), false, false, true),
+                        modified(KeyCode::Char(']'), false, false, true)]);
+    }
+
+    #[test]
+    fn escape() {
+        let decoder = Decoder::new(Vec::new());
+        // wait to see if more is coming
+        assert_eq!(decoder.decode(b"\x1b", false), None);
+        assert_eq!(decoder.decode(b"\x1b[1;", false), None);
+        assert_eq!(keys(b"\x1b"), vec![key(KeyCode::Esc)]);
+        assert_eq!(keys(b"\x1ba\x1b\x1b"),
+                   vec![modified(KeyCode::Char('a'), false, true, false),
+                        modified(KeyCode::Esc, false, true, false)]);
+        assert_eq!(keys(b"\x1b\x1b\x1b"),
+                   vec![modified(KeyCode::Esc, false, true, false), key(KeyCode::Esc)]);
+        // a sequence that was cut short
+        assert_eq!(keys(b"\x1b["), vec![modified(KeyCode::Char('['), false, true, false)]);
+    }
+
+    #[test]
+    fn sequences() {
+        assert_eq!(keys(b"\x1b[A\x1bOD\x1b[H\x1b[4~\x1b[5~\x1b[Z"),
+                   vec![key(KeyCode::Up), key(KeyCode::Left), key(KeyCode::Home),
+                        key(KeyCode::End), key(KeyCode::PageUp), key(KeyCode::BackTab)]);
+        assert_eq!(keys(b"\x1bOP\x1b[1;2S\x1b[24~\x1b[34~\x1b[[E"),
+                   vec![key(KeyCode::F(1)), modified(KeyCode::F(4), true, false, false),
+                        key(KeyCode::F(12)), key(KeyCode::F(20)), key(KeyCode::F(5))]);
+        // modifier parameters, and alt as a prefix
+        assert_eq!(keys(b"\x1b[1;5C\x1b[3;3~\x1b\x1b[B\x1b[97;6u"),
+                   vec![modified(KeyCode::Right, false, false, true),
+                        modified(KeyCode::Delete, false, true, false),
+                        modified(KeyCode::Down, false, true, false),
+                        modified(KeyCode::Char('a'), true, false, true)]);
+        // code points past the basic multilingual plane
+        assert_eq!(keys(b"\x1b[128512u"), vec![key(KeyCode::Char('\u{1f600}'))]);
+        // from terminfo, and unknown sequences that are skipped
+        assert_eq!(keys(b"\x1bOw\x1b[<0;1;1M\x1b[99~x"),
+                   vec![key(KeyCode::F(24)), key(KeyCode::Char('x'))]);
+        // rxvt style, which only terminfo can tell the end of
+        assert_eq!(keys(b"\x1b[23$\x1b\x1b[23$"),
+                   vec![key(KeyCode::F(21)), modified(KeyCode::F(21), false, true, false)]);
+        let decoder = Decoder::new(vec![(b"\x1b[23$".to_vec(), KeyCode::F(21))]);
+        assert_eq!(decoder.decode(b"\x1b[23", false), None);
+    }
+}
@@ -1,16 +1,25 @@
 extern crate term;
 #[macro_use]
 extern crate error_chain;
+extern crate unicode_width;
 
 mod error;
 mod cap;
 mod sys;
+mod guard;
+mod track;
+mod input;
 
 pub use error::*;
-pub use cap::{Capability, Color};
+pub use cap::{Capability, Color, ColorChoice, CursorShape};
+pub use guard::{HyperlinkGuard, CursorGuard, HiddenCursorGuard, CursorStyleGuard,
+                AlternateScreenGuard, TitleGuard, SynchronizedUpdateGuard, InputModeGuard};
+pub use input::{KeyCode, KeyEvent, Modifiers};
 use cap::CAPABILITIES;
+use track::Tracker;
 
-use std::io;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
 
 /// A struct representing the dimensions of a terminal
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -19,6 +28,39 @@ pub struct Dimensions {
     pub columns: u16
 }
 
+/// The size of the terminal window in pixels, and of one character cell
+///
+/// The window size only covers the grid of cells, not any padding or scrollbars around it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PixelDimensions {
+    pub width: u16,
+    pub height: u16,
+    pub cell_width: u16,
+    pub cell_height: u16,
+}
+
+impl PixelDimensions {
+    /// Work out the cell size from the size of the window
+    pub(crate) fn from_window(width: u16, height: u16, dimensions: Dimensions) -> Self {
+        PixelDimensions {
+            width,
+            height,
+            cell_width: width / dimensions.columns.max(1),
+            cell_height: height / dimensions.rows.max(1),
+        }
+    }
+
+    /// Work out the size of the window from the cell size
+    pub(crate) fn from_cell(cell_width: u16, cell_height: u16, dimensions: Dimensions) -> Self {
+        PixelDimensions {
+            width: cell_width.saturating_mul(dimensions.columns),
+            height: cell_height.saturating_mul(dimensions.rows),
+            cell_width,
+            cell_height,
+        }
+    }
+}
+
 /// A struct representing a position in the terminal window (e.g. cursor)
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct Position {
@@ -31,15 +73,79 @@ pub struct Position {
 /// The terminal is stateful - you set attribues, write some text, and reset attributes 
 /// to their defaults with `reset`. Stateless libraries could be built on top of this.
 pub struct Terminal<T> {
-   inner: sys::Terminal<T>
+   inner: sys::Terminal<T>,
+   color_choice: ColorChoice,
+   /// Whether the stream is connected to a terminal
+   tty: bool,
+   /// Whether setting attributes has any effect, worked out from the color choice
+   styled: bool,
+   /// Where we think the cursor is, from what has been written
+   tracker: Tracker,
+   /// The tty's settings from before raw or cbreak mode, put back when dropped
+   input_mode: Option<sys::SavedInputMode>,
+   /// Input read from the terminal that hasn't been turned into keys yet
+   reader: input::Reader,
+   /// Where keys are read from, opened when the first one is read
+   input: Option<sys::Input>,
 }
 
 impl<T: io::Write + Send> Terminal<T> {
     /// Create a new terminal from 
+    ///
+    /// There's no telling what an arbitrary stream is connected to, so with `ColorChoice::Auto`
+    /// text is only styled if `CLICOLOR_FORCE` says to. Use `stdout` or `stderr` to style text
+    /// when they are connected to a terminal.
     #[inline]
     pub fn new(stream: T) -> Result<Self> {
+        Self::with_tty(stream, false)
+    }
+
+    fn with_tty(stream: T, tty: bool) -> Result<Self> {
         let inner = sys::Terminal::new(stream)?;
-        Ok(Terminal { inner })
+        let color_choice = ColorChoice::default();
+        let tracker = Tracker::new(inner.dimensions().ok());
+        let reader = input::Reader::new(inner.keys());
+        Ok(Terminal {
+            inner,
+            color_choice,
+            tty,
+            styled: color_choice.should_style(tty),
+            tracker,
+            input_mode: None,
+            reader,
+            input: None,
+        })
+    }
+
+    /// Get the policy for whether text is styled
+    #[inline]
+    pub fn color_choice(&self) -> ColorChoice {
+        self.color_choice
+    }
+
+    /// Set the policy for whether text is styled
+    ///
+    /// If this turns styling off, the attributes are reset and any hyperlink is ended first.
+    pub fn set_color_choice(&mut self, choice: ColorChoice) -> Result<()> {
+        let styled = choice.should_style(self.tty);
+        if self.styled && !styled {
+            self.inner.reset()?;
+            self.inner.end_hyperlink()?;
+        }
+        self.color_choice = choice;
+        self.styled = styled;
+        Ok(())
+    }
+
+    /// Set an attribute, unless the color choice says not to style text
+    #[inline]
+    fn set_attr(&mut self, attr: sys::Attr) -> Result<()> {
+        self.tracker.style_changed();
+        if self.styled {
+            self.inner.set_attr(attr)
+        } else {
+            Ok(())
+        }
     }
 
     /// Check whether this terminal has a particular capability
@@ -81,10 +187,22 @@ impl<T: io::Write + Send> Terminal<T> {
             .collect()
     }
 
-    /// Reset the terminal attributes to their defaults (for most options, this is "off")
+    /// Reset the terminal attributes to their defaults (for most options, this is "off"). This
+    /// also turns autowrap back on and insert mode off, if they were changed.
     #[inline]
     pub fn reset(&mut self) -> Result<()> {
-        self.inner.reset()
+        self.tracker.style_changed();
+        if !self.tracker.autowrap() {
+            self.set_autowrap(true)?;
+        }
+        if self.tracker.insert_mode() {
+            self.set_insert_mode(false)?;
+        }
+        if self.styled {
+            self.inner.reset()
+        } else {
+            Ok(())
+        }
     }
 
     /// Get the color that will be used to color text.
@@ -96,7 +214,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set the terminal foreground color
     #[inline]
     pub fn set_foreground_color(&mut self, color: Color) -> Result<()> {
-        self.inner.set_attr(sys::Attr::ForegroundColor(color))
+        self.set_attr(sys::Attr::ForegroundColor(color))
     }
 
     /// Get the color that will be used to color the area behind text.
@@ -108,7 +226,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set the terminal background color
     #[inline]
     pub fn set_background_color(&mut self, color: Color) -> Result<()> {
-        self.inner.set_attr(sys::Attr::BackgroundColor(color))
+        self.set_attr(sys::Attr::BackgroundColor(color))
     }
 
     /// Get whether text will be written in bold
@@ -120,7 +238,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set bold text on or off
     #[inline]
     pub fn set_bold(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Bold(on))
+        self.set_attr(sys::Attr::Bold(on))
     }
 
     /// Get whether text will be written with lower brightness
@@ -132,7 +250,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set writing dim text on or off
     #[inline]
     pub fn set_dim(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Dim(on))
+        self.set_attr(sys::Attr::Dim(on))
     }
 
     /// Get whether text will be written in italics
@@ -144,7 +262,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set italic text on or off
     #[inline]
     pub fn set_italic(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Italic(on))
+        self.set_attr(sys::Attr::Italic(on))
     }
 
     /// Get whether text will be written underlined
@@ -156,7 +274,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set writing underlined text on or off
     #[inline]
     pub fn set_underline(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Underline(on))
+        self.set_attr(sys::Attr::Underline(on))
     }
 
     /// Get whether text will be written underlined
@@ -168,7 +286,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set writing underlined text on or off
     #[inline]
     pub fn set_blink(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Blink(on))
+        self.set_attr(sys::Attr::Blink(on))
     }
 
     /// Get whether text will be written underlined
@@ -180,7 +298,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set writing underlined text on or off
     #[inline]
     pub fn set_standout(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Standout(on))
+        self.set_attr(sys::Attr::Standout(on))
     }
 
     /// Get whether text will be written underlined
@@ -192,7 +310,7 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set writing underlined text on or off
     #[inline]
     pub fn set_reverse(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Reverse(on))
+        self.set_attr(sys::Attr::Reverse(on))
     }
 
     /// Get whether text will be written underlined
@@ -204,37 +322,898 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set writing underlined text on or off
     #[inline]
     pub fn set_secure(&mut self, on: bool) -> Result<()> {
-        self.inner.set_attr(sys::Attr::Secure(on))
+        self.set_attr(sys::Attr::Secure(on))
+    }
+
+    /// Get whether text will be written struck through
+    #[inline]
+    pub fn strikethrough(&self) -> Result<bool> {
+        self.inner.strikethrough()
+    }
+
+    /// Set struck through text on or off
+    #[inline]
+    pub fn set_strikethrough(&mut self, on: bool) -> Result<()> {
+        self.set_attr(sys::Attr::Strikethrough(on))
+    }
+
+    /// Get whether text will be written with a line above it
+    #[inline]
+    pub fn overline(&self) -> Result<bool> {
+        self.inner.overline()
+    }
+
+    /// Set overlined text on or off
+    #[inline]
+    pub fn set_overline(&mut self, on: bool) -> Result<()> {
+        self.set_attr(sys::Attr::Overline(on))
+    }
+
+    /// Get whether text will be concealed
+    #[inline]
+    pub fn conceal(&self) -> Result<bool> {
+        self.inner.conceal()
+    }
+
+    /// Set concealed text on or off
+    #[inline]
+    pub fn set_conceal(&mut self, on: bool) -> Result<()> {
+        self.set_attr(sys::Attr::Conceal(on))
+    }
+
+    /// Turn off bold and dim, without changing any other attributes
+    #[inline]
+    pub fn set_normal_intensity(&mut self) -> Result<()> {
+        self.set_attr(sys::Attr::NormalIntensity)
+    }
+
+    /// Prints `prompt`, then reads a line from the terminal without echoing it, for entering
+    /// passwords.
+    ///
+    /// The terminal is put back how it was afterwards, even if reading fails or panics. The line
+    /// is returned without its line ending.
+    pub fn read_password(&mut self, prompt: &str) -> Result<String> {
+        write!(self, "{}", prompt)?;
+        self.flush()?;
+        let password = sys::read_password()?;
+        // the newline the user typed wasn't echoed
+        writeln!(self)?;
+        Ok(password)
+    }
+
+    /// Changes how the tty handles input, after putting back any change made before so that
+    /// the original settings are the ones restored
+    fn set_input_mode(&mut self, mode: sys::InputMode) -> Result<()> {
+        self.restore_input_mode()?;
+        self.flush()?;
+        let saved = sys::set_input_mode(mode)?;
+        self.tracker.set_newline_returns(saved.newline_returns());
+        self.input_mode = Some(saved);
+        Ok(())
+    }
+
+    /// Puts the terminal in raw mode, returning a guard that puts it back when dropped
+    ///
+    /// In raw mode keys are read one at a time without being echoed, and ctrl-c, ctrl-z etc. are
+    /// read as keys instead of sending signals. Output isn't processed either, so a newline only
+    /// moves the cursor down: write `\r\n` to go to the start of the next line.
+    ///
+    /// The settings are changed on the controlling terminal (`/dev/tty` on unix), which is
+    /// usually the one this terminal writes to. They are put back when the guard or the
+    /// terminal is dropped, including when unwinding from a panic, but not if the process exits
+    /// without running destructors, e.g. with `std::process::exit`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut term = term.enable_raw_mode()?;
+    /// write!(term, "press any key\r\n")?;
+    /// ```
+    pub fn enable_raw_mode<'a>(&'a mut self) -> Result<InputModeGuard<'a, T>> {
+        self.set_input_mode(sys::InputMode::Raw)?;
+        Ok(InputModeGuard::new(self))
+    }
+
+    /// Puts the terminal in cbreak mode, returning a guard that puts it back when dropped
+    ///
+    /// In cbreak mode keys are read one at a time without being echoed, but ctrl-c etc. still
+    /// send signals and output is processed as normal. The settings are put back in the same
+    /// way as for `enable_raw_mode`.
+    pub fn enable_cbreak_mode<'a>(&'a mut self) -> Result<InputModeGuard<'a, T>> {
+        self.set_input_mode(sys::InputMode::Cbreak)?;
+        Ok(InputModeGuard::new(self))
+    }
+
+    /// Puts the tty's input settings back how they were before `enable_raw_mode` or
+    /// `enable_cbreak_mode`. Does nothing if they haven't been changed.
+    pub fn restore_input_mode(&mut self) -> Result<()> {
+        if self.input_mode.is_some() {
+            // text already written should be processed the way it was written
+            self.flush()?;
+            self.input_mode = None;
+            self.tracker.set_newline_returns(true);
+        }
+        Ok(())
+    }
+
+    /// Waits for a key to be pressed, and returns it
+    ///
+    /// Keys are read from the controlling terminal, and should be read in raw or cbreak mode
+    /// (see `enable_raw_mode`), or else they only arrive once enter is pressed. Input that
+    /// arrived while waiting for the reply to a query (see `take_pending_input`) is read first.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut term = term.enable_raw_mode()?;
+    /// loop {
+    ///     match term.read_key()? {
+    ///         KeyEvent { code: KeyCode::Char('q'), .. } => break,
+    ///         key => write!(term, "{:?}\r\n", key)?,
+    ///     }
+    /// }
+    /// ```
+    pub fn read_key(&mut self) -> Result<KeyEvent> {
+        loop {
+            if let Some(key) = self.next_key(None)? {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for a key to be pressed, returning `None` if none was. See
+    /// `read_key`.
+    pub fn poll_key(&mut self, timeout: Duration) -> Result<Option<KeyEvent>> {
+        self.next_key(Some(timeout))
+    }
+
+    /// Sets how long to wait for the rest of an escape sequence before deciding that the Esc
+    /// key was pressed on its own. The default is 100 milliseconds, which may need to be longer
+    /// over slow connections.
+    #[inline]
+    pub fn set_escape_timeout(&mut self, timeout: Duration) {
+        self.reader.escape_timeout = timeout;
+    }
+
+    fn next_key(&mut self, timeout: Option<Duration>) -> Result<Option<KeyEvent>> {
+        let pending = self.inner.take_pending_input();
+        self.reader.push(&pending);
+        // the terminal should see everything written before the key it's waiting for
+        self.flush()?;
+        let start = Instant::now();
+        let mut input = Vec::new();
+        loop {
+            if let Some(key) = self.reader.next_key(false) {
+                return Ok(Some(key));
+            }
+            let remaining = timeout.map(|timeout| {
+                timeout.checked_sub(start.elapsed()).unwrap_or(Duration::from_secs(0))
+            });
+            // the start of an escape sequence is only waited on for a moment, in case it was
+            // the Esc key, and never past the timeout
+            let wait = if self.reader.is_partial() {
+                let escape_timeout = self.reader.escape_timeout;
+                Some(remaining.map_or(escape_timeout, |remaining| remaining.min(escape_timeout)))
+            } else {
+                remaining
+            };
+            input.clear();
+            if self.input()?.read(wait, &mut input)? {
+                self.reader.push(&input);
+            } else if self.reader.is_partial() {
+                if let Some(key) = self.reader.next_key(true) {
+                    return Ok(Some(key));
+                }
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Where keys are read from, opening it the first time
+    fn input(&mut self) -> Result<&mut sys::Input> {
+        if self.input.is_none() {
+            self.input = Some(sys::Input::open()?);
+        }
+        Ok(self.input.as_mut().unwrap())
+    }
+
+    fn move_cursor(&mut self, motion: sys::Motion) -> Result<()> {
+        self.inner.move_cursor(motion)?;
+        self.tracker.motion(motion);
+        Ok(())
+    }
+
+    /// Moves the cursor up `n` lines, stopping at the top of the screen
+    #[inline]
+    pub fn cursor_up(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::Up(n))
+    }
+
+    /// Moves the cursor down `n` lines, stopping at the bottom of the screen
+    #[inline]
+    pub fn cursor_down(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::Down(n))
+    }
+
+    /// Moves the cursor left `n` columns, stopping at the start of the line
+    #[inline]
+    pub fn cursor_left(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::Left(n))
+    }
+
+    /// Moves the cursor right `n` columns, stopping at the end of the line
+    #[inline]
+    pub fn cursor_right(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::Right(n))
+    }
+
+    /// Moves the cursor to the start of the line `n` lines down
+    #[inline]
+    pub fn next_line(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::NextLine(n))
+    }
+
+    /// Moves the cursor to the start of the line `n` lines up
+    #[inline]
+    pub fn prev_line(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::PrevLine(n))
+    }
+
+    /// Moves the cursor to a column (counting from 0) in the current row
+    #[inline]
+    pub fn set_column(&mut self, column: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::Column(column))
+    }
+
+    /// Moves the cursor to a row (counting from 0) in the current column
+    #[inline]
+    pub fn set_row(&mut self, row: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::Row(row))
+    }
+
+    /// Moves the cursor forward `n` tab stops
+    #[inline]
+    pub fn tab_forward(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::TabForward(n))
+    }
+
+    /// Moves the cursor back `n` tab stops
+    #[inline]
+    pub fn tab_back(&mut self, n: u16) -> Result<()> {
+        self.move_cursor(sys::Motion::TabBack(n))
+    }
+
+    /// Sets a tab stop at the cursor column
+    #[inline]
+    pub fn set_tab_stop(&mut self) -> Result<()> {
+        self.inner.tab_stop(sys::TabStop::Set)?;
+        self.tracker.set_custom_tabs();
+        Ok(())
+    }
+
+    /// Clears the tab stop at the cursor column, if there is one
+    #[inline]
+    pub fn clear_tab_stop(&mut self) -> Result<()> {
+        self.inner.tab_stop(sys::TabStop::Clear)?;
+        self.tracker.set_custom_tabs();
+        Ok(())
+    }
+
+    /// Clears all tab stops
+    #[inline]
+    pub fn clear_all_tab_stops(&mut self) -> Result<()> {
+        self.inner.tab_stop(sys::TabStop::ClearAll)?;
+        self.tracker.set_custom_tabs();
+        Ok(())
+    }
+
+    /// Saves the cursor position, so it can be returned to with `restore_cursor`
+    ///
+    /// Only one position is saved at a time.
+    #[inline]
+    pub fn save_cursor(&mut self) -> Result<()> {
+        self.inner.save_cursor()?;
+        self.tracker.save();
+        Ok(())
+    }
+
+    /// Moves the cursor back to the position saved by `save_cursor`
+    #[inline]
+    pub fn restore_cursor(&mut self) -> Result<()> {
+        self.inner.restore_cursor()?;
+        self.tracker.restore();
+        Ok(())
+    }
+
+    /// Saves the cursor position, returning a guard that moves the cursor back there when
+    /// dropped
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// {
+    ///     let mut term = term.saved_cursor()?;
+    ///     term.set_position(Position { row: 0, column: 70 })?;
+    ///     write!(term, "12:00")?;
+    /// }
+    /// // back where we were
+    /// ```
+    #[inline]
+    pub fn saved_cursor<'a>(&'a mut self) -> Result<CursorGuard<'a, T>> {
+        self.save_cursor()?;
+        Ok(CursorGuard::new(self))
+    }
+
+    /// Hides the cursor
+    #[inline]
+    pub fn hide_cursor(&mut self) -> Result<()> {
+        self.inner.set_cursor_visible(false)
+    }
+
+    /// Shows the cursor
+    #[inline]
+    pub fn show_cursor(&mut self) -> Result<()> {
+        self.inner.set_cursor_visible(true)
+    }
+
+    /// Hides the cursor, returning a guard that shows it again when dropped (including when a
+    /// panic unwinds past it)
+    #[inline]
+    pub fn hidden_cursor<'a>(&'a mut self) -> Result<HiddenCursorGuard<'a, T>> {
+        self.hide_cursor()?;
+        Ok(HiddenCursorGuard::new(self))
+    }
+
+    /// Sets the shape of the cursor
+    #[inline]
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        self.inner.set_cursor_shape(shape)
+    }
+
+    /// Sets the cursor back to the shape the user has configured
+    #[inline]
+    pub fn reset_cursor_shape(&mut self) -> Result<()> {
+        self.inner.set_cursor_shape(CursorShape::Default)
+    }
+
+    /// Sets the color of the cursor
+    #[inline]
+    pub fn set_cursor_color(&mut self, color: Color) -> Result<()> {
+        self.inner.set_cursor_color(Some(color))
+    }
+
+    /// Sets the cursor back to the color the user has configured
+    #[inline]
+    pub fn reset_cursor_color(&mut self) -> Result<()> {
+        self.inner.set_cursor_color(None)
+    }
+
+    /// Resets both the shape and the color of the cursor, as far as the terminal supports
+    pub fn reset_cursor_style(&mut self) -> Result<()> {
+        if self.has_capability(&Capability::CursorShape) {
+            self.reset_cursor_shape()?;
+        }
+        if self.has_capability(&Capability::CursorColor) {
+            self.reset_cursor_color()?;
+        }
+        Ok(())
+    }
+
+    /// Returns a guard that resets the shape and color of the cursor when dropped, so they can
+    /// be changed temporarily
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut term = term.cursor_style();
+    /// term.set_cursor_shape(CursorShape::SteadyBar)?;
+    /// ```
+    #[inline]
+    pub fn cursor_style<'a>(&'a mut self) -> CursorStyleGuard<'a, T> {
+        CursorStyleGuard::new(self)
+    }
+
+    /// Rings the bell, which may beep or flash depending on how the terminal is set up
+    #[inline]
+    pub fn bell(&mut self) -> Result<()> {
+        self.inner.bell()
     }
-    
-    /// Moves the cursor up one line
+
+    /// Flashes the screen instead of beeping
+    #[inline]
+    pub fn visual_bell(&mut self) -> Result<()> {
+        self.inner.visual_bell()
+    }
+
+    /// Sends a desktop notification, e.g. to say a long job has finished while the terminal is
+    /// in the background
+    ///
+    /// This depends on the terminal emulator, so check `Capability::Notification` first, or
+    /// fall back to `bell`. The title and body can't contain control characters.
+    #[inline]
+    pub fn notify(&mut self, title: &str, body: &str) -> Result<()> {
+        self.inner.notify(title, body)
+    }
+
+    /// Turns wrapping at the end of the line on or off. It starts out on.
+    ///
+    /// With autowrap off, text that reaches the last column keeps overwriting it instead of
+    /// going on to the next line. This is how to write in the bottom right cell without
+    /// scrolling the screen on terminals that would scroll as soon as it's written.
+    pub fn set_autowrap(&mut self, on: bool) -> Result<()> {
+        self.inner.set_autowrap(on)?;
+        self.tracker.set_autowrap(on);
+        Ok(())
+    }
+
+    /// Turns insert mode on or off. It starts out off.
+    ///
+    /// In insert mode, text written pushes the rest of the line right instead of replacing it,
+    /// and anything pushed past the end of the line is lost.
+    pub fn set_insert_mode(&mut self, on: bool) -> Result<()> {
+        self.inner.set_insert_mode(on)?;
+        self.tracker.set_insert_mode(on);
+        Ok(())
+    }
+
+    /// Sets the title of the window, which terminals with tabs usually show on the tab. This
+    /// also sets the icon name, where there is one.
+    #[inline]
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        self.inner.set_title(title)
+    }
+
+    /// Sets the name shown when the window is minimized. On Windows, where there isn't one,
+    /// this does nothing.
+    #[inline]
+    pub fn set_icon_name(&mut self, name: &str) -> Result<()> {
+        self.inner.set_icon_name(name)
+    }
+
+    /// Saves the title and icon name on a stack, so they can be put back with `pop_title`.
+    /// Terminals without a title stack ignore this.
+    #[inline]
+    pub fn push_title(&mut self) -> Result<()> {
+        self.inner.push_title()
+    }
+
+    /// Puts back the title and icon name most recently saved by `push_title`
+    #[inline]
+    pub fn pop_title(&mut self) -> Result<()> {
+        self.inner.pop_title()
+    }
+
+    /// Saves the title, returning a guard that puts it back when dropped, so it can be changed
+    /// for as long as the program runs
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut term = term.saved_title()?;
+    /// for i in 0..100 {
+    ///     term.set_title(&format!("building: {}%", i))?;
+    /// }
+    /// ```
+    #[inline]
+    pub fn saved_title<'a>(&'a mut self) -> Result<TitleGuard<'a, T>> {
+        self.push_title()?;
+        Ok(TitleGuard::new(self))
+    }
+
+    /// Switches to the alternate screen, returning a guard that switches back when dropped
+    /// (including when a panic unwinds past it)
+    ///
+    /// The alternate screen has no scrollback, and when leaving it the normal screen is shown
+    /// as it was, so full screen programs don't leave anything behind.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut screen = term.enter_alternate_screen()?;
+    /// screen.clear_screen()?;
+    /// write!(screen, "drawn on the alternate screen")?;
+    /// ```
+    #[inline]
+    pub fn enter_alternate_screen<'a>(&'a mut self) -> Result<AlternateScreenGuard<'a, T>> {
+        self.inner.set_alternate_screen(true)?;
+        self.tracker.forget();
+        Ok(AlternateScreenGuard::new(self))
+    }
+
+    /// Switches back from the alternate screen to the normal screen
     #[inline]
-    pub fn cursor_up(&mut self) -> Result<()> {
-        self.inner.cursor_up()
+    pub fn leave_alternate_screen(&mut self) -> Result<()> {
+        self.inner.set_alternate_screen(false)?;
+        self.tracker.forget();
+        Ok(())
+    }
+
+    /// Limits scrolling to the rows from `top` to `bottom` (counting from 0, inclusive), and
+    /// moves the cursor to the top left
+    ///
+    /// Text written past the bottom of the region scrolls only the region, so rows above and
+    /// below it can be used for a fixed header and footer. On Windows, the region only limits
+    /// `scroll_up`, `scroll_down` and `reverse_index`.
+    pub fn set_scroll_region(&mut self, top: u16, bottom: u16) -> Result<()> {
+        if top >= bottom {
+            bail!("the scroll region must be at least two rows");
+        }
+        self.inner.set_scroll_region(Some((top, bottom)))?;
+        self.tracker.set_scroll_region(Some((top, bottom)));
+        Ok(())
+    }
+
+    /// Lets the whole screen scroll again, and moves the cursor to the top left
+    #[inline]
+    pub fn reset_scroll_region(&mut self) -> Result<()> {
+        self.inner.set_scroll_region(None)?;
+        self.tracker.set_scroll_region(None);
+        Ok(())
+    }
+
+    /// Scrolls the text in the scroll region up `n` lines without moving the cursor, adding
+    /// blank lines at the bottom
+    #[inline]
+    pub fn scroll_up(&mut self, n: u16) -> Result<()> {
+        self.inner.scroll_up(n)?;
+        self.tracker.scroll(n as i32);
+        Ok(())
+    }
+
+    /// Scrolls the text in the scroll region down `n` lines without moving the cursor, adding
+    /// blank lines at the top
+    #[inline]
+    pub fn scroll_down(&mut self, n: u16) -> Result<()> {
+        self.inner.scroll_down(n)?;
+        self.tracker.scroll(-(n as i32));
+        Ok(())
+    }
+
+    /// Moves the cursor up a line, scrolling the text down if the cursor is at the top of the
+    /// scroll region
+    #[inline]
+    pub fn reverse_index(&mut self) -> Result<()> {
+        self.inner.reverse_index()?;
+        self.tracker.reverse_index();
+        Ok(())
+    }
+
+    /// Inserts or deletes lines or characters, or erases characters, at the cursor
+    fn edit(&mut self, edit: sys::Edit) -> Result<()> {
+        self.inner.edit(edit)?;
+        self.tracker.edit(edit);
+        Ok(())
+    }
+
+    /// Inserts `n` blank lines at the cursor, pushing the line the cursor is on and the ones
+    /// below it down (IL). Lines pushed past the bottom of the scroll region are lost. The
+    /// cursor moves to the start of the line.
+    #[inline]
+    pub fn insert_lines(&mut self, n: u16) -> Result<()> {
+        self.edit(sys::Edit::InsertLines(n))
+    }
+
+    /// Deletes `n` lines starting with the one the cursor is on, pulling the ones below up and
+    /// adding blank lines at the bottom of the scroll region (DL). The cursor moves to the start
+    /// of the line.
+    #[inline]
+    pub fn delete_lines(&mut self, n: u16) -> Result<()> {
+        self.edit(sys::Edit::DeleteLines(n))
+    }
+
+    /// Inserts `n` blank characters at the cursor, pushing the rest of the line right (ICH).
+    /// Characters pushed past the end of the line are lost.
+    #[inline]
+    pub fn insert_chars(&mut self, n: u16) -> Result<()> {
+        self.edit(sys::Edit::InsertChars(n))
+    }
+
+    /// Deletes `n` characters starting at the cursor, pulling the rest of the line left (DCH)
+    #[inline]
+    pub fn delete_chars(&mut self, n: u16) -> Result<()> {
+        self.edit(sys::Edit::DeleteChars(n))
+    }
+
+    /// Blanks out `n` characters starting at the cursor, without moving anything (ECH)
+    #[inline]
+    pub fn erase_chars(&mut self, n: u16) -> Result<()> {
+        self.edit(sys::Edit::EraseChars(n))
+    }
+
+    /// Clears part of the screen, in the current background color
+    fn clear(&mut self, clear: sys::Clear) -> Result<()> {
+        self.inner.clear(clear)?;
+        match clear {
+            sys::Clear::Screen => {
+                self.tracker.forget_contents();
+                self.tracker.set_position(Position { row: 0, column: 0 });
+            }
+            sys::Clear::ToEndOfLine => self.tracker.erase_to_end_of_line(),
+            // we don't keep the scrollback
+            sys::Clear::Scrollback => (),
+            _ => self.tracker.forget_contents(),
+        }
+        Ok(())
     }
 
     /// Deletes the text from the cursor location to the end of the line
+    #[deprecated(note = "this clears to the end of the line rather than deleting it, use \
+                         `clear_to_end_of_line` or `delete_lines` instead")]
     #[inline]
     pub fn delete_line(&mut self) -> Result<()> {
-        self.inner.delete_line()
+        self.clear_to_end_of_line()
+    }
+
+    /// Clears the line from the cursor to the end
+    #[inline]
+    pub fn clear_to_end_of_line(&mut self) -> Result<()> {
+        self.clear(sys::Clear::ToEndOfLine)
+    }
+
+    /// Clears the whole screen, and moves the cursor to the top left
+    #[inline]
+    pub fn clear_screen(&mut self) -> Result<()> {
+        self.clear(sys::Clear::Screen)
+    }
+
+    /// Clears the screen from the cursor to the end, including the rest of the line the cursor
+    /// is on
+    #[inline]
+    pub fn clear_to_end_of_screen(&mut self) -> Result<()> {
+        self.clear(sys::Clear::ToEndOfScreen)
+    }
+
+    /// Clears the screen from the start up to and including the cursor
+    #[inline]
+    pub fn clear_to_start_of_screen(&mut self) -> Result<()> {
+        self.clear(sys::Clear::ToStartOfScreen)
+    }
+
+    /// Clears the whole line the cursor is on, without moving the cursor
+    #[inline]
+    pub fn clear_line(&mut self) -> Result<()> {
+        self.clear(sys::Clear::Line)
+    }
+
+    /// Clears the line from the start up to and including the cursor
+    #[inline]
+    pub fn clear_to_start_of_line(&mut self) -> Result<()> {
+        self.clear(sys::Clear::ToStartOfLine)
+    }
+
+    /// Clears the lines that have scrolled off the top of the screen (ED 3), leaving what is on
+    /// the screen
+    #[inline]
+    pub fn clear_scrollback(&mut self) -> Result<()> {
+        self.clear(sys::Clear::Scrollback)
     }
 
     /// Returns the cursor to the beginning of the current line
     #[inline]
     pub fn carriage_return(&mut self) -> Result<()> {
-        self.inner.carriage_return()
+        self.inner.carriage_return()?;
+        self.tracker.motion(sys::Motion::Column(0));
+        Ok(())
     }
 
     /// Gets the current cursor position from top-left
+    ///
+    /// On unix this asks the terminal where the cursor is, and waits for it to reply (see
+    /// `set_query_timeout`). Any input typed in the meantime can be recovered with
+    /// `take_pending_input`. The position is also used to correct `tracked_position`.
     #[inline]
-    pub fn position(&self) -> Result<Position> {
-        self.inner.position()
+    pub fn position(&mut self) -> Result<Position> {
+        let position = self.inner.position()?;
+        self.update_tracked_dimensions();
+        self.tracker.set_position(position);
+        Ok(position)
     }
 
-    /// Gets the current cursor position from top-left
+    /// Picks up any change in the size of the terminal, which affects where lines wrap
+    fn update_tracked_dimensions(&mut self) {
+        if let Ok(dimensions) = self.inner.dimensions() {
+            self.tracker.set_dimensions(dimensions);
+        }
+    }
+
+    /// Gets where the cursor is thought to be, without asking the terminal
+    ///
+    /// The position is worked out from the text and cursor movements written through this
+    /// terminal, so it is cheap but only an estimate. It starts out unknown, and becomes known
+    /// after `set_position`, `position`, or moving to an absolute row and column. It becomes
+    /// unknown again after writing anything whose effect on the cursor can't be predicted, such
+    /// as a raw escape sequence that moves it. Anything written to the terminal by other means
+    /// (e.g. echoed input or another process) isn't seen.
+    #[inline]
+    pub fn tracked_position(&self) -> Option<Position> {
+        self.tracker.position()
+    }
+
+    /// Sets how long to wait for the terminal to reply when asking it something, e.g. the cursor
+    /// position. The default is half a second.
+    #[inline]
+    pub fn set_query_timeout(&mut self, timeout: Duration) {
+        self.inner.set_query_timeout(timeout)
+    }
+
+    /// Takes any input that arrived while waiting for the terminal to reply to a query, so that
+    /// it isn't lost
     #[inline]
+    pub fn take_pending_input(&mut self) -> Vec<u8> {
+        self.inner.take_pending_input()
+    }
+
+    /// Moves the cursor to a position, counting from the top-left
+    ///
+    /// When we know where the cursor is, it is moved with whichever sequence is shortest:
+    /// absolute positioning, relative motions, returning to the start of the line or the top
+    /// left corner first, or writing again the text that is already between here and there.
+    ///
+    /// If the terminal can't move straight to a position, the cursor is moved a row and a column
+    /// at a time, or relative to where it is now (see `tracked_position`). Only if the terminal
+    /// can't do any of these, or it can't move the cursor in the direction needed (e.g. a
+    /// terminal that can only return to the start of the line and move down), is
+    /// `NotSupported(Position)` returned.
     pub fn set_position(&mut self, position: Position) -> Result<()> {
-        self.inner.set_position(position)
+        self.update_tracked_dimensions();
+        if self.move_cursor_from_tracked(position)? {
+            // moved the cheapest way
+        } else if self.has_capability(&Capability::Position) {
+            self.inner.set_position(position)?;
+        } else if self.has_capability(&Capability::AxisPosition) {
+            self.inner.move_cursor(sys::Motion::Row(position.row))?;
+            self.inner.move_cursor(sys::Motion::Column(position.column))?;
+        } else {
+            self.emulate_position(position)?;
+        }
+        self.tracker.set_position(position);
+        Ok(())
+    }
+
+    /// Moves the cursor to a position with whatever sequence is shortest, starting from where
+    /// we think the cursor is. Returns `false` if we don't know where it is.
+    fn move_cursor_from_tracked(&mut self, position: Position) -> Result<bool> {
+        let from = match self.tracker.position() {
+            // terminals don't agree on where relative motions go from here
+            Some(_) if self.tracker.pending_wrap() => return Ok(false),
+            Some(from) => from,
+            None => return Ok(false),
+        };
+        let reprint = sys::Reprint {
+            from_column: self.tracker.reprint(position.row, from.column, position.column),
+            from_start: self.tracker.reprint(position.row, 0, position.column),
+        };
+        self.inner.move_cursor_from(from, position, &reprint)
+    }
+
+    /// Moves the cursor to a position with relative motions, carriage returns and newlines,
+    /// starting from where we think the cursor is
+    fn emulate_position(&mut self, position: Position) -> Result<()> {
+        let current = match self.tracker.position() {
+            Some(current) => current,
+            // asking the terminal is the last resort, since it may mean waiting for the timeout
+            None => self.position()
+                .map_err(|_| Error::from(ErrorKind::NotSupported(Capability::Position)))?,
+        };
+        // relative motions and newlines stop at the margins of the scroll region, so check
+        // before writing anything
+        if !self.tracker.can_reach_row(current.row, position.row) {
+            bail!(ErrorKind::NotSupported(Capability::Position));
+        }
+        let relative = self.has_capability(&Capability::RelativeMotion);
+        if !relative {
+            // all we can do is go down with newlines, and back to the start of the line
+            let column = if position.row > current.row { 0 } else { current.column };
+            if position.row < current.row
+                || (position.column != column && position.column != 0)
+            {
+                bail!(ErrorKind::NotSupported(Capability::Position));
+            }
+            for _ in current.row..position.row {
+                if self.tracker.newline_returns() {
+                    // the tty turns each newline into a carriage return and a newline
+                    self.write_all(b"\n")?;
+                } else {
+                    self.write_all(b"\r\n")?;
+                }
+            }
+            if position.column != column {
+                self.carriage_return()?;
+            }
+            return Ok(());
+        }
+        if position.row < current.row {
+            self.move_cursor(sys::Motion::Up(current.row - position.row))?;
+        } else if position.row > current.row {
+            self.move_cursor(sys::Motion::Down(position.row - current.row))?;
+        }
+        if position.column == 0 && current.column != 0 {
+            self.carriage_return()?;
+        } else if position.column > current.column {
+            self.move_cursor(sys::Motion::Right(position.column - current.column))?;
+        } else if position.column < current.column {
+            self.move_cursor(sys::Motion::Left(current.column - position.column))?;
+        }
+        Ok(())
+    }
+
+    /// Starts a synchronized update, so that nothing written until `end_synchronized_update` is
+    /// drawn until then, and the whole update appears at once instead of tearing.
+    ///
+    /// The first time this is called the terminal may be asked whether it supports synchronized
+    /// output, which waits for a reply. If it doesn't, this does nothing and everything is drawn
+    /// as it is written.
+    #[inline]
+    pub fn begin_synchronized_update(&mut self) -> Result<()> {
+        self.inner.begin_synchronized_update()
+    }
+
+    /// Ends the current synchronized update, so the terminal draws it
+    #[inline]
+    pub fn end_synchronized_update(&mut self) -> Result<()> {
+        self.inner.end_synchronized_update()
+    }
+
+    /// Starts a synchronized update, returning a guard that ends it when dropped
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// {
+    ///     let mut update = term.synchronized_update()?;
+    ///     update.clear_screen()?;
+    ///     write!(update, "the next frame")?;
+    /// }
+    /// term.flush()?;
+    /// ```
+    #[inline]
+    pub fn synchronized_update<'a>(&'a mut self) -> Result<SynchronizedUpdateGuard<'a, T>> {
+        self.begin_synchronized_update()?;
+        Ok(SynchronizedUpdateGuard::new(self))
+    }
+
+    /// Starts a hyperlink to `url`, so that text written until `end_hyperlink` is clickable.
+    ///
+    /// Links with the same `id` are treated as one link by the terminal, even if they are split
+    /// up (e.g. across lines). If the terminal doesn't support hyperlinks, or the color choice
+    /// says not to style text, this does nothing and the text is written as normal.
+    #[inline]
+    pub fn begin_hyperlink(&mut self, url: &str, id: Option<&str>) -> Result<()> {
+        if self.styled {
+            self.inner.begin_hyperlink(url, id)?;
+        }
+        // text in a link can't be reprinted outside it, or the other way round
+        self.tracker.style_changed();
+        Ok(())
+    }
+
+    /// Ends the current hyperlink
+    #[inline]
+    pub fn end_hyperlink(&mut self) -> Result<()> {
+        if self.styled {
+            self.inner.end_hyperlink()?;
+        }
+        self.tracker.style_changed();
+        Ok(())
+    }
+
+    /// Starts a hyperlink, returning a guard that ends it when dropped
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// {
+    ///     let mut link = term.hyperlink("https://example.com", None)?;
+    ///     write!(link, "example.com")?;
+    /// }
+    /// ```
+    #[inline]
+    pub fn hyperlink<'a>(&'a mut self, url: &str, id: Option<&str>)
+        -> Result<HyperlinkGuard<'a, T>>
+    {
+        self.begin_hyperlink(url, id)?;
+        Ok(HyperlinkGuard::new(self))
     }
 
     /// Gets the dimensions of the terminal
@@ -243,6 +1222,29 @@ impl<T: io::Write + Send> Terminal<T> {
         self.inner.dimensions()
     }
 
+    /// Gets the size of the terminal window in pixels, and the size of a character cell, e.g.
+    /// to draw images or keep the aspect ratio of a graph
+    ///
+    /// If the tty doesn't know the size in pixels this asks the terminal, which waits for a
+    /// reply. A terminal that never answers blocks for twice the query timeout (see
+    /// `set_query_timeout`), after which this gives `NoResponse` once and `NotSupported` from
+    /// then on.
+    #[inline]
+    pub fn pixel_dimensions(&mut self) -> Result<PixelDimensions> {
+        self.inner.pixel_dimensions()
+    }
+
+    /// Asks the terminal to resize its window to `rows` by `columns` cells
+    ///
+    /// Many terminal emulators ignore this, or only allow it when configured to, so check
+    /// `dimensions` afterwards to see what size the window actually is.
+    pub fn resize_window(&mut self, rows: u16, columns: u16) -> Result<()> {
+        if rows == 0 || columns == 0 {
+            bail!("cannot resize the window to {} rows by {} columns", rows, columns);
+        }
+        self.inner.resize_window(Dimensions { rows, columns })
+    }
+
     /// Gets an immutable reference to the wrapped stream
     #[inline]
     pub fn get_ref(&self) -> &T {
@@ -264,7 +1266,9 @@ impl<T: io::Write + Send> Terminal<T> {
 
 impl<T: io::Write> io::Write for Terminal<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner.write(buf)
+        let written = self.inner.write(buf)?;
+        self.tracker.write(&buf[..written]);
+        Ok(written)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -274,18 +1278,40 @@ impl<T: io::Write> io::Write for Terminal<T> {
 
 /// Create a terminal wrapping stdout
 pub fn stdout() -> Result<Terminal<io::Stdout>> {
-    Terminal::new(io::stdout())
+    Terminal::with_tty(io::stdout(), sys::is_tty(sys::StdStream::Stdout))
 }
 
 /// Create a terminal wrapping stderr
 pub fn stderr() -> Result<Terminal<io::Stderr>> {
-    Terminal::new(io::stderr())
+    Terminal::with_tty(io::stderr(), sys::is_tty(sys::StdStream::Stderr))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::env;
+    use std::io::Write;
+
+    use super::{ColorChoice, Terminal};
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn unstyled_hyperlink() {
+        env::set_var("TERM", "xterm");
+        env::set_var("FORCE_HYPERLINK", "1");
+        let mut term = Terminal::new(Vec::new()).unwrap();
+        term.set_color_choice(ColorChoice::Never).unwrap();
+        term.flush().unwrap();
+        term.get_mut().clear();
+        {
+            let mut link = term.hyperlink("https://example.com", None).unwrap();
+            write!(link, "example.com").unwrap();
+        }
+        term.flush().unwrap();
+        assert_eq!(term.get_ref(), b"example.com");
+    }
 }
@@ -0,0 +1,78 @@
+//! Detection of features that terminfo doesn't describe, by looking at the environment the
+//! terminal emulator sets up
+
+use std::env;
+
+/// Get an environment variable, treating empty as unset
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().and_then(|v| if v.is_empty() { None } else { Some(v) })
+}
+
+/// Get an environment variable holding a version number
+fn version(name: &str) -> Option<u32> {
+    var(name).and_then(|v| v.parse().ok())
+}
+
+/// Whether the terminal emulator understands OSC 8 hyperlinks
+pub fn hyperlinks() -> bool {
+    if let Some(force) = var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+    if let Some(program) = var("TERM_PROGRAM") {
+        match program.as_str() {
+            "iTerm.app" | "WezTerm" | "vscode" | "Hyper" | "ghostty" => return true,
+            _ => (),
+        }
+    }
+    if var("KITTY_WINDOW_ID").is_some() || var("WT_SESSION").is_some()
+        || var("DOMTERM").is_some()
+    {
+        return true;
+    }
+    // VTE got hyperlinks in 0.50, konsole in 20.04
+    if version("VTE_VERSION").map(|v| v >= 5000).unwrap_or(false)
+        || version("KONSOLE_VERSION").map(|v| v >= 200400).unwrap_or(false)
+    {
+        return true;
+    }
+    match var("TERM") {
+        Some(term) => ["kitty", "foot", "alacritty", "wezterm", "contour"].iter()
+            .any(|name| term.contains(name)),
+        None => false,
+    }
+}
+
+/// The escape sequences terminal emulators use for desktop notifications
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Notifications {
+    /// `OSC 9 ; body`, from iTerm2
+    Osc9,
+    /// `OSC 777 ; notify ; title ; body`, from urxvt
+    Osc777,
+    /// `OSC 99`, kitty's notification protocol
+    Osc99,
+}
+
+/// Which kind of desktop notifications the terminal emulator understands, if any
+pub fn notifications() -> Option<Notifications> {
+    let term = var("TERM").unwrap_or_default();
+    if var("KITTY_WINDOW_ID").is_some() || term.contains("kitty") {
+        return Some(Notifications::Osc99);
+    }
+    match var("TERM_PROGRAM").as_ref().map(|p| p.as_str()) {
+        Some("iTerm.app") => return Some(Notifications::Osc9),
+        Some("WezTerm") | Some("ghostty") => return Some(Notifications::Osc777),
+        _ => (),
+    }
+    if term.contains("foot") || term.starts_with("rxvt") {
+        Some(Notifications::Osc777)
+    } else {
+        None
+    }
+}
+
+/// Whether the terminal understands OSC sequences, e.g. for changing colors or the title (as
+/// opposed to ignoring them or printing garbage). Only the linux console is known not to.
+pub fn osc() -> bool {
+    var("TERM").map(|term| term != "linux").unwrap_or(false)
+}
@@ -1,16 +1,65 @@
 //! Platform-specific implementation of a terminal
 
 use cap::Capability;
-use error::{ErrorKind, Result};
-use {Position, Dimensions};
+use error::Result;
+#[cfg(windows)]
+use error::ErrorKind;
+use input::KeyCode;
+use {Position, Dimensions, PixelDimensions};
 
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+mod terminfo;
+#[cfg(unix)]
+mod detect;
+#[cfg(unix)]
+use self::terminfo::TerminfoTerminal;
 #[cfg(windows)]
 mod win;
 #[cfg(windows)]
 use self::win::WinConsole;
 
+#[cfg(unix)]
+pub use self::unix::{read_password, set_input_mode, Input, SavedInputMode};
+#[cfg(windows)]
+pub use self::win::{read_password, set_input_mode, Input, SavedInputMode};
+
 use std::io;
-use cap::Color;
+use std::time::Duration;
+use cap::{Color, CursorShape};
+
+/// The standard streams that can be written to
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StdStream {
+    Stdout,
+    Stderr,
+}
+
+/// How the terminal handles input
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InputMode {
+    /// Every key is passed straight through, including ctrl-c, and output isn't processed
+    Raw,
+    /// Keys are passed through as they're typed without being echoed, but ctrl-c still
+    /// interrupts and output is processed as normal
+    Cbreak,
+}
+
+/// Whether a standard stream is connected to a terminal
+#[cfg(unix)]
+pub fn is_tty(stream: StdStream) -> bool {
+    unix::is_tty(match stream {
+        StdStream::Stdout => 1,
+        StdStream::Stderr => 2,
+    })
+}
+
+/// Whether a standard stream is connected to a terminal
+#[cfg(windows)]
+pub fn is_tty(stream: StdStream) -> bool {
+    win::is_console(stream)
+}
 
 /// Terminal attributes that it is possible to set
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -23,6 +72,11 @@ pub enum Attr {
     Standout(bool),
     Reverse(bool),
     Secure(bool),
+    Strikethrough(bool),
+    Overline(bool),
+    Conceal(bool),
+    /// Turns off both bold and dim
+    NormalIntensity,
     ForegroundColor(Color),
     BackgroundColor(Color)
 }
@@ -40,20 +94,129 @@ impl Into<Capability> for Attr {
             Attr::Standout(_) => Capability::Standout,
             Attr::Reverse(_) => Capability::Reverse,
             Attr::Secure(_) => Capability::Secure,
+            Attr::Strikethrough(_) => Capability::Strikethrough,
+            Attr::Overline(_) => Capability::Overline,
+            Attr::Conceal(_) => Capability::Conceal,
+            Attr::NormalIntensity => Capability::NormalIntensity,
             Attr::ForegroundColor(_) => Capability::ForegroundColor,
             Attr::BackgroundColor(_) => Capability::BackgroundColor
         }
     }
 }
 
+/// Parts of the screen that can be cleared
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Clear {
+    /// The whole screen, moving the cursor to the top left
+    Screen,
+    /// From the cursor to the end of the screen
+    ToEndOfScreen,
+    /// From the start of the screen to the cursor
+    ToStartOfScreen,
+    /// The whole line the cursor is on
+    Line,
+    /// From the cursor to the end of the line
+    ToEndOfLine,
+    /// From the start of the line to the cursor
+    ToStartOfLine,
+    /// The lines that have scrolled off the top of the screen
+    Scrollback,
+}
+
+impl Into<Capability> for Clear {
+    fn into(self) -> Capability {
+        match self {
+            Clear::Screen | Clear::ToEndOfScreen | Clear::ToStartOfScreen => {
+                Capability::ClearScreen
+            }
+            Clear::Line | Clear::ToEndOfLine | Clear::ToStartOfLine => Capability::ClearLine,
+            Clear::Scrollback => Capability::ClearScrollback,
+        }
+    }
+}
+
+/// Ways of changing the text at the cursor
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Edit {
+    /// Insert blank lines, pushing the line the cursor is on and those below it down
+    InsertLines(u16),
+    /// Delete lines from the one the cursor is on, pulling those below up
+    DeleteLines(u16),
+    /// Insert blank characters, pushing the rest of the line right
+    InsertChars(u16),
+    /// Delete characters, pulling the rest of the line left
+    DeleteChars(u16),
+    /// Blank out characters, without moving the rest of the line
+    EraseChars(u16),
+}
+
+impl Into<Capability> for Edit {
+    fn into(self) -> Capability {
+        match self {
+            Edit::InsertLines(_) | Edit::DeleteLines(_) => Capability::InsertDeleteLines,
+            Edit::InsertChars(_) | Edit::DeleteChars(_) => Capability::InsertDeleteChars,
+            Edit::EraseChars(_) => Capability::EraseChars,
+        }
+    }
+}
+
+/// Ways of moving the cursor without giving a full position
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Motion {
+    Up(u16),
+    Down(u16),
+    Left(u16),
+    Right(u16),
+    /// Down, to the start of the line
+    NextLine(u16),
+    /// Up, to the start of the line
+    PrevLine(u16),
+    /// To a column in the current row
+    Column(u16),
+    /// To a row in the current column
+    Row(u16),
+    /// Forward a number of tab stops
+    TabForward(u16),
+    /// Back a number of tab stops
+    TabBack(u16),
+}
+
+/// Text that moves the cursor right along the row it is moving to by writing what is already
+/// there, when that is cheaper than a motion
+#[derive(Debug, Clone, Default)]
+pub struct Reprint {
+    /// From the column the cursor is in
+    pub from_column: Option<Vec<u8>>,
+    /// From the start of the row
+    pub from_start: Option<Vec<u8>>,
+}
+
+/// Ways of changing the tab stops
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TabStop {
+    /// Set a tab stop at the cursor
+    Set,
+    /// Clear the tab stop at the cursor
+    Clear,
+    /// Clear all tab stops
+    ClearAll,
+}
+
 /// Inner terminal with platform-specific implementations
 pub enum Terminal<T> {
-    //TerminfoTerminal(TerminfoTerminal),
+    #[cfg(unix)]
+    Terminfo(TerminfoTerminal<T>),
     #[cfg(windows)]
     WinConsole(WinConsole<T>)
 }
 
 impl<T: io::Write + Send> Terminal<T> {
+    #[cfg(unix)]
+    pub fn new(stream: T) -> Result<Self> {
+        Ok(Terminal::Terminfo(TerminfoTerminal::new(stream)?))
+    }
+
+    #[cfg(windows)]
     pub fn new(stream: T) -> Result<Self> {
         Ok(Terminal::WinConsole(WinConsole::new(stream)?))
     }
@@ -64,6 +227,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Gets the foreground color
     pub fn foreground_color(&self) -> Result<Color> {
         match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => Ok(term.foreground_color()),
+            #[cfg(windows)]
             &Terminal::WinConsole(ref console) => Ok(console.foreground_color()),
         }
     }
@@ -71,6 +237,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Gets the background color
     pub fn background_color(&self) -> Result<Color> {
         match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => Ok(term.background_color()),
+            #[cfg(windows)]
             &Terminal::WinConsole(ref console) => Ok(console.background_color()),
         }
     }
@@ -78,6 +247,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Set the state of an attribute
     pub fn set_attr(&mut self, attr: Attr) -> Result<()> {
         match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_attr(attr),
+            #[cfg(windows)]
             &mut Terminal::WinConsole(ref mut console) => console.set_attr(attr),
         }
     }
@@ -85,6 +257,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Check for support for an attribute
     pub fn has_capability(&self, cap: &Capability) -> bool {
         match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => term.has_capability(*cap),
+            #[cfg(windows)]
             &Terminal::WinConsole(ref console) => console.has_capability(*cap)
         }
     }
@@ -92,6 +267,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Reset the terminal to default values
     pub fn reset(&mut self) -> Result<()> {
         match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.reset(),
+            #[cfg(windows)]
             &mut Terminal::WinConsole(ref mut console) => console.reset(),
         }
     }
@@ -99,50 +277,438 @@ impl<T: io::Write + Send> Terminal<T> {
     /// True if bold is set, false if not
     pub fn bold(&self) -> Result<bool> {
         match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => Ok(term.attr(Capability::Bold)),
+            #[cfg(windows)]
             &Terminal::WinConsole(_) => {
                 bail!(ErrorKind::NotSupported(Capability::Bold));
             }
         }
     }
 
-    /// Moves the cursor up one line
-    pub fn cursor_up(&mut self) -> Result<()> {
+    /// True if strikethrough is set, false if not
+    pub fn strikethrough(&self) -> Result<bool> {
+        match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => Ok(term.attr(Capability::Strikethrough)),
+            #[cfg(windows)]
+            &Terminal::WinConsole(_) => {
+                bail!(ErrorKind::NotSupported(Capability::Strikethrough));
+            }
+        }
+    }
+
+    /// True if overline is set, false if not
+    pub fn overline(&self) -> Result<bool> {
+        match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => Ok(term.attr(Capability::Overline)),
+            #[cfg(windows)]
+            &Terminal::WinConsole(_) => {
+                bail!(ErrorKind::NotSupported(Capability::Overline));
+            }
+        }
+    }
+
+    /// True if conceal is set, false if not
+    pub fn conceal(&self) -> Result<bool> {
+        match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => Ok(term.attr(Capability::Conceal)),
+            #[cfg(windows)]
+            &Terminal::WinConsole(_) => {
+                bail!(ErrorKind::NotSupported(Capability::Conceal));
+            }
+        }
+    }
+
+    /// Moves the cursor relative to where it is
+    pub fn move_cursor(&mut self, motion: Motion) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.move_cursor(motion),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.move_cursor(motion),
+        }
+    }
+
+    /// Changes the tab stops
+    pub fn tab_stop(&mut self, change: TabStop) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.tab_stop(change),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => bail!(ErrorKind::NotSupported(Capability::TabStops)),
+        }
+    }
+
+    /// Saves the cursor position
+    pub fn save_cursor(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.save_cursor(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.save_cursor(),
+        }
+    }
+
+    /// Moves the cursor back to the saved position
+    pub fn restore_cursor(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.restore_cursor(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.restore_cursor(),
+        }
+    }
+
+    /// Shows or hides the cursor
+    pub fn set_cursor_visible(&mut self, visible: bool) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_cursor_visible(visible),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.set_cursor_visible(visible),
+        }
+    }
+
+    /// Sets the shape of the cursor
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_cursor_shape(shape),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.set_cursor_shape(shape),
+        }
+    }
+
+    /// Sets the color of the cursor, or resets it with `None`
+    pub fn set_cursor_color(&mut self, color: Option<Color>) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_cursor_color(color),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => bail!(ErrorKind::NotSupported(Capability::CursorColor)),
+        }
+    }
+
+    /// Switches to or from the alternate screen
+    pub fn set_alternate_screen(&mut self, on: bool) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_alternate_screen(on),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.set_alternate_screen(on),
+        }
+    }
+
+    /// Limits scrolling to the rows from `top` to `bottom` inclusive, or the whole screen, and
+    /// moves the cursor to the top left
+    pub fn set_scroll_region(&mut self, region: Option<(u16, u16)>) -> Result<()> {
         match self {
-            &mut Terminal::WinConsole(ref mut console) => console.cursor_up(),
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_scroll_region(region),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.set_scroll_region(region),
         }
     }
 
-    /// Deletes the text from the cursor location to the end of the line
-    pub fn delete_line(&mut self) -> Result<()> {
+    /// Scrolls the text in the scroll region up `n` lines
+    pub fn scroll_up(&mut self, n: u16) -> Result<()> {
         match self {
-            &mut Terminal::WinConsole(ref mut console) => console.delete_line(),
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.scroll_up(n),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.scroll(n as i32),
+        }
+    }
+
+    /// Scrolls the text in the scroll region down `n` lines
+    pub fn scroll_down(&mut self, n: u16) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.scroll_down(n),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.scroll(-(n as i32)),
+        }
+    }
+
+    /// Moves the cursor up a line, scrolling down if it is at the top of the scroll region
+    pub fn reverse_index(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.reverse_index(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.reverse_index(),
+        }
+    }
+
+    /// Inserts or deletes lines or characters at the cursor, or erases characters
+    pub fn edit(&mut self, edit: Edit) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.edit(edit),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.edit(edit),
+        }
+    }
+
+    /// Sets the title of the window, and its icon name
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_title(title),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.set_title(title),
+        }
+    }
+
+    /// Sets the icon name of the window, where there is one
+    pub fn set_icon_name(&mut self, name: &str) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_icon_name(name),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => {
+                let _ = name;
+                Ok(())
+            }
+        }
+    }
+
+    /// Saves the title, so it can be put back with `pop_title`
+    pub fn push_title(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.push_title(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.push_title(),
+        }
+    }
+
+    /// Puts back the title saved by `push_title`
+    pub fn pop_title(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.pop_title(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.pop_title(),
+        }
+    }
+
+    /// Rings the bell
+    pub fn bell(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.bell(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.bell(),
+        }
+    }
+
+    /// Flashes the screen
+    pub fn visual_bell(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.visual_bell(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => bail!(ErrorKind::NotSupported(Capability::VisualBell)),
+        }
+    }
+
+    /// Sends a desktop notification
+    pub fn notify(&mut self, title: &str, body: &str) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.notify(title, body),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => {
+                let _ = (title, body);
+                bail!(ErrorKind::NotSupported(Capability::Notification))
+            }
+        }
+    }
+
+    /// Clears part of the screen
+    pub fn clear(&mut self, clear: Clear) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.clear(clear),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.clear(clear),
+        }
+    }
+
+    /// Turns wrapping at the end of the line on or off
+    pub fn set_autowrap(&mut self, on: bool) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_autowrap(on),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.set_autowrap(on),
+        }
+    }
+
+    /// Turns insert mode on or off
+    pub fn set_insert_mode(&mut self, on: bool) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_insert_mode(on),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => {
+                let _ = on;
+                bail!(ErrorKind::NotSupported(Capability::InsertMode))
+            }
         }
     }
 
     /// Return to the beginning of the current line
     pub fn carriage_return(&mut self) -> Result<()> {
         match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.carriage_return(),
+            #[cfg(windows)]
             &mut Terminal::WinConsole(ref mut console) => console.carriage_return(),
         }
     }
 
     /// Gets the current position of the cursor
-    pub fn position(&self) -> Result<Position> {
+    pub fn position(&mut self) -> Result<Position> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.position(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref console) => console.position(),
+        }
+    }
+
+    /// Sets how long to wait for the terminal to reply to a query
+    pub fn set_query_timeout(&mut self, timeout: Duration) {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_query_timeout(timeout),
+            // The console is queried through the windows api, which doesn't need a timeout
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => (),
+        }
+    }
+
+    /// The sequences the terminal's keys send, where they're known
+    pub fn keys(&self) -> Vec<(Vec<u8>, KeyCode)> {
+        match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => term.keys(),
+            // With virtual terminal input, the console sends the usual xterm sequences
+            #[cfg(windows)]
+            &Terminal::WinConsole(_) => Vec::new(),
+        }
+    }
+
+    /// Takes the input that arrived while waiting for replies to queries
+    pub fn take_pending_input(&mut self) -> Vec<u8> {
         match self {
-            &Terminal::WinConsole(ref console) => console.position(),
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.take_pending_input(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => Vec::new(),
+        }
+    }
+
+    /// Moves the cursor from a known position to another in as few bytes as possible,
+    /// returning `false` if the terminal has no way of doing so
+    pub fn move_cursor_from(&mut self, from: Position, to: Position, reprint: &Reprint)
+        -> Result<bool>
+    {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.move_cursor_from(from, to, reprint),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => {
+                let _ = (from, reprint);
+                console.set_position(to)?;
+                Ok(true)
+            }
         }
     }
 
     /// Sets the position of the cursor
     pub fn set_position(&mut self, pos: Position) -> Result<()> {
         match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.set_position(pos),
+            #[cfg(windows)]
             &mut Terminal::WinConsole(ref mut console) => console.set_position(pos),
         }
     }
 
+    /// Starts a synchronized update, if the terminal supports them
+    pub fn begin_synchronized_update(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.begin_synchronized_update(),
+            // Console API calls take effect straight away, there's nothing to hold back
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => Ok(()),
+        }
+    }
+
+    /// Ends the current synchronized update, if there is one
+    pub fn end_synchronized_update(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.end_synchronized_update(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => Ok(()),
+        }
+    }
+
+    /// Starts a hyperlink, if the terminal supports them
+    pub fn begin_hyperlink(&mut self, url: &str, id: Option<&str>) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.begin_hyperlink(url, id),
+            // The console can't do links, text is written as normal
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => Ok(()),
+        }
+    }
+
+    /// Ends the current hyperlink, if there is one
+    pub fn end_hyperlink(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.end_hyperlink(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(_) => Ok(()),
+        }
+    }
+
+    /// Gets the size of the window and its cells in pixels
+    pub fn pixel_dimensions(&mut self) -> Result<PixelDimensions> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.pixel_dimensions(),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref console) => console.pixel_dimensions(),
+        }
+    }
+
+    /// Resizes the window, if the terminal allows it
+    pub fn resize_window(&mut self, dimensions: Dimensions) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.resize_window(dimensions),
+            #[cfg(windows)]
+            &mut Terminal::WinConsole(ref mut console) => console.resize_window(dimensions),
+        }
+    }
+
     /// Gets the dimensions of the terminal
     pub fn dimensions(&self) -> Result<Dimensions> {
         match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => term.dimensions(),
+            #[cfg(windows)]
             &Terminal::WinConsole(ref console) => console.dimensions(),
         }
     }
@@ -150,6 +716,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Get immutable reference to underlying stream
     pub fn get_ref(&self) -> &T {
         match self {
+            #[cfg(unix)]
+            &Terminal::Terminfo(ref term) => term.get_ref(),
+            #[cfg(windows)]
             &Terminal::WinConsole(ref console) => console.get_ref(),
         }
     }
@@ -157,6 +726,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Get mutable reference to underlying stream
     pub fn get_mut(&mut self) -> &mut T {
         match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.get_mut(),
+            #[cfg(windows)]
             &mut Terminal::WinConsole(ref mut console) => console.get_mut(),
         }
     }
@@ -164,6 +736,9 @@ impl<T: io::Write + Send> Terminal<T> {
     /// Get mutable reference to underlying stream
     pub fn into_inner(self) -> T {
         match self {
+            #[cfg(unix)]
+            Terminal::Terminfo(term) => term.into_inner(),
+            #[cfg(windows)]
             Terminal::WinConsole(console) => console.into_inner(),
         }
     }
@@ -172,12 +747,18 @@ impl<T: io::Write + Send> Terminal<T> {
 impl<T: io::Write> io::Write for Terminal<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.write(buf),
+            #[cfg(windows)]
             &mut Terminal::WinConsole(ref mut console) => console.write(buf),
         }
     }
 
     fn flush(&mut self) -> io::Result<()> {
         match self {
+            #[cfg(unix)]
+            &mut Terminal::Terminfo(ref mut term) => term.flush(),
+            #[cfg(windows)]
             &mut Terminal::WinConsole(ref mut console) => console.flush(),
         }
     }
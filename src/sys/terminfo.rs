@@ -0,0 +1,1375 @@
+//! Terminal handling using the terminfo database
+
+use std::io::prelude::*;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use term::terminfo::TermInfo;
+use term::terminfo::parm::{expand, Param, Variables};
+
+use cap::{Capability, Color, CursorShape};
+use error::{ErrorKind, Result};
+use super::{Attr, Clear, Edit, Motion, Reprint, TabStop};
+use super::{detect, unix};
+use input::KeyCode;
+use {Position, Dimensions, PixelDimensions};
+
+/// A set of text attributes. Terminfo cannot tell us what is set, so we keep track ourselves.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+struct Attrs {
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    blink: bool,
+    standout: bool,
+    reverse: bool,
+    secure: bool,
+    strikethrough: bool,
+    overline: bool,
+    conceal: bool,
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+impl Attrs {
+    /// Record the effect of setting an attribute
+    fn update(&mut self, attr: Attr) {
+        match attr {
+            Attr::Bold(on) => self.bold = on,
+            Attr::Dim(on) => self.dim = on,
+            Attr::Italic(on) => self.italic = on,
+            Attr::Underline(on) => self.underline = on,
+            Attr::Blink(on) => self.blink = on,
+            Attr::Standout(on) => self.standout = on,
+            Attr::Reverse(on) => self.reverse = on,
+            Attr::Secure(on) => self.secure = on,
+            Attr::Strikethrough(on) => self.strikethrough = on,
+            Attr::Overline(on) => self.overline = on,
+            Attr::Conceal(on) => self.conceal = on,
+            Attr::NormalIntensity => {
+                self.bold = false;
+                self.dim = false;
+            }
+            Attr::ForegroundColor(c) => self.foreground = Some(c),
+            Attr::BackgroundColor(c) => self.background = Some(c),
+        }
+    }
+}
+
+/// The shorter of two ways of doing something, if either is possible
+fn cheapest(a: Option<Vec<u8>>, b: Option<Vec<u8>>) -> Option<Vec<u8>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.len() < a.len() { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The hex color for a color enum in xterm's default palette, for OSC sequences
+fn color_spec(color: Color) -> &'static str {
+    match color {
+        Color::Black => "#000000",
+        Color::Red => "#cd0000",
+        Color::Green => "#00cd00",
+        Color::Yellow => "#cdcd00",
+        Color::Blue => "#0000ee",
+        Color::Magenta => "#cd00cd",
+        Color::Cyan => "#00cdcd",
+        Color::White => "#e5e5e5",
+        Color::BrightRed => "#ff0000",
+        Color::BrightGreen => "#00ff00",
+        Color::BrightYellow => "#ffff00",
+        Color::BrightBlue => "#5c5cff",
+        Color::BrightMagenta => "#ff00ff",
+        Color::BrightCyan => "#00ffff",
+        Color::BrightWhite => "#ffffff",
+    }
+}
+
+/// Format an SGR sequence
+fn sgr(params: &[u16]) -> Vec<u8> {
+    let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+    format!("\x1b[{}m", params.join(";")).into_bytes()
+}
+
+/// Split an expanded capability at its `$<delay>` padding, into pieces of text and the
+/// milliseconds to wait after each. The `*` and `/` flags are ignored, so proportional delays
+/// are only waited for once.
+fn split_padding(cmd: &[u8]) -> Vec<(&[u8], u64)> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < cmd.len() {
+        if cmd[i..].starts_with(b"$<") {
+            let spec = &cmd[i + 2..];
+            if let Some(end) = spec.iter().position(|&b| b == b'>') {
+                let spec = &spec[..end];
+                let digits = spec.iter().take_while(|b| b.is_ascii_digit()).count();
+                let well_formed = digits > 0 && spec[digits..].iter().all(|&b| {
+                    b.is_ascii_digit() || b == b'.' || b == b'*' || b == b'/'
+                });
+                if well_formed {
+                    let delay = spec[..digits].iter().fold(0u64, |delay, &b| {
+                        delay.saturating_mul(10).saturating_add((b - b'0') as u64)
+                    });
+                    pieces.push((&cmd[start..i], delay));
+                    i += end + 3;
+                    start = i;
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+    pieces.push((&cmd[start..], 0));
+    pieces
+}
+
+/// Convert the color enum to its index in the terminal's palette
+fn color_index(color: Color) -> u16 {
+    match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::White => 7,
+        Color::BrightRed => 9,
+        Color::BrightGreen => 10,
+        Color::BrightYellow => 11,
+        Color::BrightBlue => 12,
+        Color::BrightMagenta => 13,
+        Color::BrightCyan => 14,
+        Color::BrightWhite => 15,
+    }
+}
+
+/// A Terminal implementation that looks up escape sequences in the terminfo database.
+pub struct TerminfoTerminal<T> {
+    out: T,
+    info: TermInfo,
+    /// Whether the terminal understands ECMA-48 SGR sequences, which lets us use attributes
+    /// terminfo has no capability for
+    ansi: bool,
+    /// Whether the terminal understands OSC 8 hyperlinks
+    hyperlinks: bool,
+    /// Whether we are in the middle of writing a hyperlink
+    in_hyperlink: bool,
+    /// The rows scrolling is limited to, whose margins stop relative motions
+    scroll_region: Option<(u16, u16)>,
+    /// How the terminal emulator sends desktop notifications
+    notifications: Option<detect::Notifications>,
+    /// Whether the terminal supports synchronized output, if we've asked it yet
+    synchronized_output: Option<bool>,
+    /// Whether we are in the middle of a synchronized update
+    in_synchronized_update: bool,
+    /// Whether the terminal answered when asked for its size in pixels, if we've asked it yet
+    pixel_query: Option<bool>,
+    /// The attributes that have been asked for
+    attrs: Attrs,
+    /// The attributes the terminal has, i.e. that have been written out, or `None` if something
+    /// has changed them behind our back (e.g. restoring the cursor also restores them)
+    current: Option<Attrs>,
+    /// How long to wait for the terminal to reply to a query
+    query_timeout: Duration,
+    /// Input that arrived while we were waiting for a reply to a query
+    pending_input: Vec<u8>,
+}
+
+impl<T: Write + Send> TerminfoTerminal<T> {
+    /// Returns `Err` if there is no terminfo entry for the current terminal
+    pub fn new(out: T) -> Result<TerminfoTerminal<T>> {
+        let info = match TermInfo::from_env() {
+            Ok(info) => info,
+            Err(e) => bail!("could not load the terminfo entry for this terminal: {}", e),
+        };
+        let ansi = info.strings.get("setaf")
+            .map(|setaf| setaf.starts_with(b"\x1b["))
+            .unwrap_or(false);
+        Ok(TerminfoTerminal {
+            out,
+            info,
+            ansi,
+            hyperlinks: detect::hyperlinks(),
+            in_hyperlink: false,
+            scroll_region: None,
+            notifications: detect::notifications(),
+            synchronized_output: None,
+            pixel_query: None,
+            in_synchronized_update: false,
+            attrs: Attrs::default(),
+            current: Some(Attrs::default()),
+            query_timeout: Duration::from_millis(500),
+            pending_input: Vec::new(),
+        })
+    }
+}
+
+impl<T: Write> TerminfoTerminal<T> {
+    /// Whether the terminfo entry has the named string capability
+    fn has_cap(&self, name: &str) -> bool {
+        self.info.strings.contains_key(name)
+    }
+
+    /// Get the named string capability with any parameters expanded and padding removed, or
+    /// `None` if the terminal doesn't have it
+    fn cap(&self, name: &str, params: &[Param]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.padded_cap(name, params)?.map(|cmd| {
+            split_padding(&cmd).into_iter().flat_map(|(text, _)| text).cloned().collect()
+        }))
+    }
+
+    /// Get the named string capability with any parameters expanded, keeping the padding
+    fn padded_cap(&self, name: &str, params: &[Param]) -> io::Result<Option<Vec<u8>>> {
+        match self.info.strings.get(name) {
+            Some(cmd) => expand(cmd, params, &mut Variables::new())
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, format!(
+                    "could not expand terminfo capability \"{}\": {}", name, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Write out the named string capability, expanding any parameters. Returns `false` if the
+    /// terminal does not have the capability.
+    fn apply_cap(&mut self, name: &str, params: &[Param]) -> Result<bool> {
+        match self.cap(name, params)? {
+            Some(cmd) => {
+                self.out.write_all(&cmd)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Get the palette index to use for a color, falling back to the non-bright version of the
+    /// color if the terminal only has 8 colors
+    fn color_index(&self, color: Color) -> u16 {
+        let idx = color_index(color);
+        let colors = self.info.numbers.get("colors").map(|&n| n as u16).unwrap_or(8);
+        if idx >= colors && idx >= 8 {
+            idx - 8
+        } else {
+            idx
+        }
+    }
+
+    /// The SGR parameter that sets a color, `base` being 30 for foreground or 40 for background
+    fn sgr_color(&self, color: Color, base: u16) -> u16 {
+        match self.color_index(color) {
+            idx @ 0..=7 => base + idx,
+            idx => base + 60 + idx - 8,
+        }
+    }
+
+    /// The SGR parameters that switch on everything in `attrs`, starting from a blank state
+    fn sgr_params(&self, attrs: &Attrs) -> Vec<u16> {
+        let mut params = Vec::new();
+        {
+            let mut push = |on: bool, param: u16| if on { params.push(param) };
+            push(attrs.bold, 1);
+            push(attrs.dim, 2);
+            push(attrs.italic, 3);
+            push(attrs.underline, 4);
+            push(attrs.blink, 5);
+            push(attrs.reverse || attrs.standout, 7);
+            push(attrs.secure || attrs.conceal, 8);
+            push(attrs.strikethrough, 9);
+            push(attrs.overline, 53);
+        }
+        if let Some(c) = attrs.foreground {
+            params.push(self.sgr_color(c, 30));
+        }
+        if let Some(c) = attrs.background {
+            params.push(self.sgr_color(c, 40));
+        }
+        params
+    }
+
+    /// The SGR parameters that change the attributes from `from` to `to`
+    fn sgr_params_diff(&self, from: &Attrs, to: &Attrs) -> Vec<u16> {
+        let mut params = Vec::new();
+        {
+            let mut toggle = |was: bool, is: bool, on: u16, off: u16| if was != is {
+                params.push(if is { on } else { off })
+            };
+            if (from.bold && !to.bold) || (from.dim && !to.dim) {
+                // SGR 22 turns off both bold and dim, so turn back on the one we want to keep
+                toggle(true, false, 0, 22);
+                toggle(false, to.bold, 1, 0);
+                toggle(false, to.dim, 2, 0);
+            } else {
+                toggle(from.bold, to.bold, 1, 0);
+                toggle(from.dim, to.dim, 2, 0);
+            }
+            toggle(from.italic, to.italic, 3, 23);
+            toggle(from.underline, to.underline, 4, 24);
+            toggle(from.blink, to.blink, 5, 25);
+            toggle(from.reverse || from.standout, to.reverse || to.standout, 7, 27);
+            toggle(from.secure || from.conceal, to.secure || to.conceal, 8, 28);
+            toggle(from.strikethrough, to.strikethrough, 9, 29);
+            toggle(from.overline, to.overline, 53, 55);
+        }
+        if from.foreground != to.foreground {
+            params.push(to.foreground.map(|c| self.sgr_color(c, 30)).unwrap_or(39));
+        }
+        if from.background != to.background {
+            params.push(to.background.map(|c| self.sgr_color(c, 40)).unwrap_or(49));
+        }
+        params
+    }
+
+    /// Build the escape sequence that takes the terminal from the attributes it has to the ones
+    /// that have been asked for
+    fn attr_sequence(&self, from: &Attrs, to: &Attrs) -> io::Result<Vec<u8>> {
+        if self.ansi {
+            // Either change just what's different, or reset and start again, whichever is shorter
+            let diff = self.sgr_params_diff(from, to);
+            if diff.is_empty() {
+                return Ok(Vec::new());
+            }
+            let mut reset = vec![0];
+            reset.extend(self.sgr_params(to));
+            let (diff, reset) = (sgr(&diff), sgr(&reset));
+            return Ok(if reset.len() < diff.len() { reset } else { diff });
+        }
+
+        // Terminfo can only turn most attributes on, so if any need turning off we have to
+        // reset everything and turn back on the ones we want to keep
+        let mut seq = Vec::new();
+        let mut from = *from;
+        let exits = [(from.italic && !to.italic, "ritm"),
+                     (from.underline && !to.underline, "rmul"),
+                     (from.standout && !to.standout, "rmso")];
+        let needs_reset = (from.bold && !to.bold) || (from.dim && !to.dim)
+            || (from.blink && !to.blink) || (from.reverse && !to.reverse)
+            || (from.secure && !to.secure)
+            || (from.foreground.is_some() && to.foreground.is_none())
+            || (from.background.is_some() && to.background.is_none())
+            || exits.iter().any(|&(off, name)| off && !self.has_cap(name));
+        if needs_reset {
+            match self.cap("sgr0", &[])? {
+                Some(cmd) => seq.extend(cmd),
+                None => return Err(io::Error::new(io::ErrorKind::Other,
+                                                  "the terminal cannot reset its attributes")),
+            }
+            from = Attrs::default();
+        } else {
+            for &(off, name) in exits.iter() {
+                if off {
+                    seq.extend(self.cap(name, &[])?.unwrap_or_default());
+                }
+            }
+        }
+        let ons = [(from.bold, to.bold, "bold"), (from.dim, to.dim, "dim"),
+                   (from.italic, to.italic, "sitm"), (from.underline, to.underline, "smul"),
+                   (from.blink, to.blink, "blink"), (from.standout, to.standout, "smso"),
+                   (from.reverse, to.reverse, "rev"), (from.secure, to.secure, "invis")];
+        for &(was, is, name) in ons.iter() {
+            if is && !was {
+                seq.extend(self.cap(name, &[])?.unwrap_or_default());
+            }
+        }
+        if let Some(c) = to.foreground {
+            if from.foreground != to.foreground {
+                let param = Param::Number(self.color_index(c) as i32);
+                seq.extend(self.cap("setaf", &[param])?.unwrap_or_default());
+            }
+        }
+        if let Some(c) = to.background {
+            if from.background != to.background {
+                let param = Param::Number(self.color_index(c) as i32);
+                seq.extend(self.cap("setab", &[param])?.unwrap_or_default());
+            }
+        }
+        Ok(seq)
+    }
+
+    /// Write out any attribute changes that haven't taken effect yet, as a single sequence
+    fn apply_attrs(&mut self) -> io::Result<()> {
+        let seq = match self.current {
+            Some(current) if current == self.attrs => return Ok(()),
+            Some(current) => self.attr_sequence(&current, &self.attrs)?,
+            // we don't know what the terminal has, so reset and start again
+            None => {
+                let mut seq = match self.cap("sgr0", &[])? {
+                    Some(cmd) => cmd,
+                    None if self.ansi => b"\x1b[0m".to_vec(),
+                    None => return Err(io::Error::new(io::ErrorKind::Other,
+                                                      "the terminal cannot reset its attributes")),
+                };
+                seq.extend(self.attr_sequence(&Attrs::default(), &self.attrs)?);
+                seq
+            }
+        };
+        self.out.write_all(&seq)?;
+        self.current = Some(self.attrs);
+        Ok(())
+    }
+}
+
+impl<T: Write + Send> TerminfoTerminal<T> {
+    /// Set an attribute. This doesn't write anything until some text is written, so changes
+    /// that are overridden before then cost nothing.
+    pub fn set_attr(&mut self, attr: Attr) -> Result<()> {
+        let cap = attr.into();
+        if !self.has_capability(cap) {
+            bail!(ErrorKind::NotSupported(cap));
+        }
+        self.attrs.update(attr);
+        Ok(())
+    }
+
+    /// Whether the terminal supports the given capability
+    pub fn has_capability(&self, cap: Capability) -> bool {
+        match cap {
+            Capability::Bold => self.has_cap("bold"),
+            Capability::Dim => self.has_cap("dim"),
+            Capability::Italic => self.has_cap("sitm"),
+            Capability::Underline => self.has_cap("smul"),
+            Capability::Blink => self.has_cap("blink"),
+            Capability::Standout => self.has_cap("smso"),
+            Capability::Reverse => self.has_cap("rev"),
+            Capability::Secure => self.has_cap("invis"),
+            // these are written as SGR sequences, but only where the entry says the terminal
+            // knows them. The linux console and many others ignore SGR 9 and 53.
+            Capability::Strikethrough => self.ansi && self.has_cap("smxx"),
+            Capability::Overline => self.ansi && self.has_cap("Smol"),
+            Capability::Conceal => self.ansi && self.has_cap("invis"),
+            // there's no capability for SGR 22, but it's as old as the SGRs for bold and dim
+            Capability::NormalIntensity => {
+                self.ansi && (self.has_cap("bold") || self.has_cap("dim"))
+            }
+            Capability::ForegroundColor => self.has_cap("setaf"),
+            Capability::BackgroundColor => self.has_cap("setab"),
+            Capability::Reset => self.has_cap("sgr0"),
+            Capability::Position => self.has_cap("cup"),
+            Capability::SaveCursor => self.ansi || (self.has_cap("sc") && self.has_cap("rc")),
+            Capability::CursorVisibility => {
+                self.ansi || (self.has_cap("civis") && self.has_cap("cnorm"))
+            }
+            Capability::CursorShape => self.ansi || self.has_cap("Ss"),
+            Capability::CursorColor => {
+                self.has_cap("Cs") || (self.ansi && detect::osc())
+            }
+            Capability::AxisPosition => self.ansi || (self.has_cap("hpa") && self.has_cap("vpa")),
+            // clearing a single tab stop still needs ANSI, and gives `NotSupported` without it
+            Capability::TabStops => self.has_cap("hts") && self.has_cap("tbc"),
+            Capability::RelativeMotion => ["cuu", "cud", "cub", "cuf"].iter()
+                .all(|cap| self.has_motion(cap)),
+            // terminfo has no way to clear to the start of the screen, so without ANSI that
+            // gives `NotSupported` even if the whole screen can be cleared
+            Capability::ClearScreen => self.ansi || self.has_cap("clear"),
+            Capability::ClearLine => self.ansi || (self.has_cap("el") && self.has_cap("el1")),
+            Capability::ClearScrollback => self.ansi || self.has_cap("E3"),
+            Capability::AlternateScreen => self.ansi || self.has_cap("smcup"),
+            Capability::ScrollRegion => self.ansi || self.has_cap("csr"),
+            Capability::Scroll => {
+                self.ansi || ["indn", "rin", "ri"].iter().all(|cap| self.has_cap(cap))
+            }
+            Capability::InsertDeleteLines => {
+                self.ansi || (self.has_motion("il") && self.has_motion("dl"))
+            }
+            Capability::InsertDeleteChars => {
+                self.ansi || (self.has_motion("ich") && self.has_motion("dch"))
+            }
+            Capability::EraseChars => self.ansi || self.has_cap("ech"),
+            Capability::Autowrap => self.ansi || (self.has_cap("smam") && self.has_cap("rmam")),
+            Capability::InsertMode => self.ansi || (self.has_cap("smir") && self.has_cap("rmir")),
+            Capability::Title => {
+                (self.has_cap("tsl") && self.has_cap("fsl")) || (self.ansi && detect::osc())
+            }
+            Capability::VisualBell => self.has_cap("flash"),
+            Capability::Notification => self.notifications.is_some(),
+            Capability::SynchronizedOutput => {
+                self.has_cap("Sync") || self.synchronized_output == Some(true)
+            }
+            Capability::Dimensions => unix::window_size().is_ok(),
+            // asking the terminal can take a second to find out it won't answer, so only
+            // count it once it has
+            Capability::PixelDimensions => {
+                self.pixel_query == Some(true) || unix::window_size()
+                    .map(|size| size.ws_xpixel != 0 && size.ws_ypixel != 0)
+                    .unwrap_or(false)
+            }
+            // there's no capability for XTWINOPS either, so this assumes xterm-like terminals
+            // understand it, and that the linux console (the same one that ignores OSC) doesn't
+            Capability::ResizeWindow => self.ansi && detect::osc(),
+            Capability::Hyperlink => self.hyperlinks,
+        }
+    }
+
+    /// Whether the attribute corresponding to the capability is switched on
+    pub fn attr(&self, cap: Capability) -> bool {
+        match cap {
+            Capability::Bold => self.attrs.bold,
+            Capability::Dim => self.attrs.dim,
+            Capability::Italic => self.attrs.italic,
+            Capability::Underline => self.attrs.underline,
+            Capability::Blink => self.attrs.blink,
+            Capability::Standout => self.attrs.standout,
+            Capability::Reverse => self.attrs.reverse,
+            Capability::Secure => self.attrs.secure,
+            Capability::Strikethrough => self.attrs.strikethrough,
+            Capability::Overline => self.attrs.overline,
+            Capability::Conceal => self.attrs.conceal,
+            _ => false,
+        }
+    }
+
+    /// Get the foreground color. Terminfo can't tell us the default color, so we assume white.
+    pub fn foreground_color(&self) -> Color {
+        self.attrs.foreground.unwrap_or(Color::White)
+    }
+
+    /// Get the background color. Terminfo can't tell us the default color, so we assume black.
+    pub fn background_color(&self) -> Color {
+        self.attrs.background.unwrap_or(Color::Black)
+    }
+
+    /// Reset all attributes to the terminal defaults
+    pub fn reset(&mut self) -> Result<()> {
+        self.attrs = Attrs::default();
+        self.current = Some(Attrs::default());
+        // if sgr0 is missing, at least try to reset the colors
+        if self.apply_cap("sgr0", &[])? || self.apply_cap("op", &[])? {
+            Ok(())
+        } else {
+            bail!(ErrorKind::NotSupported(Capability::Reset))
+        }
+    }
+
+    /// The single step version of a motion capability (e.g. `cuu1` for `cuu`), if it can be
+    /// used to move the cursor
+    fn step_cap(&self, cap: &str) -> Option<&[u8]> {
+        let step = self.info.strings.get(&format!("{}1", cap)[..])?;
+        // `cud1` is often a newline, which the tty may turn into a carriage return as well
+        if &step[..] == b"\n" {
+            None
+        } else {
+            Some(step)
+        }
+    }
+
+    /// Whether the cursor can be moved in the direction of the motion capability (e.g. `cuu`)
+    fn has_motion(&self, cap: &str) -> bool {
+        self.has_cap(cap) || self.step_cap(cap).is_some()
+    }
+
+    /// Move the cursor `n` steps using the motion capability (e.g. `cuu`), or by repeating its
+    /// single step version
+    fn apply_motion(&mut self, cap: &str, n: u16) -> Result<()> {
+        if n == 0 {
+            // the parameterised capabilities treat 0 as 1
+            return Ok(());
+        }
+        let step = self.step_cap(cap).map(|step| step.to_owned());
+        if n == 1 || !self.has_cap(cap) {
+            if let Some(step) = step {
+                for _ in 0..n {
+                    self.out.write_all(&step)?;
+                }
+                return Ok(());
+            }
+        }
+        if self.apply_cap(cap, &[Param::Number(n as i32)])? {
+            Ok(())
+        } else {
+            bail!(ErrorKind::NotSupported(Capability::RelativeMotion))
+        }
+    }
+
+    /// The cheapest sequence that moves the cursor `n` steps with the motion capability (e.g.
+    /// `cuu`), either parameterised or by repeating the single step version
+    fn motion_bytes(&self, cap: &str, n: u16) -> io::Result<Option<Vec<u8>>> {
+        if n == 0 {
+            return Ok(Some(Vec::new()));
+        }
+        let repeated = self.step_cap(cap).map(|step| step.repeat(n as usize));
+        let param = if self.has_cap(cap) {
+            self.cap(cap, &[Param::Number(n as i32)])?
+        } else {
+            None
+        };
+        Ok(cheapest(repeated, param))
+    }
+
+    /// The sequence that moves the cursor to a column or row using the capability (`hpa` or
+    /// `vpa`), or the given ECMA-48 sequence
+    fn axis_bytes(&self, cap: &str, n: u16, ansi_final: char) -> io::Result<Option<Vec<u8>>> {
+        match self.cap(cap, &[Param::Number(n as i32)])? {
+            Some(seq) => Ok(Some(seq)),
+            None if self.ansi => Ok(Some(format!("\x1b[{}{}", n + 1, ansi_final).into_bytes())),
+            None => Ok(None),
+        }
+    }
+
+    /// The cheapest sequence that moves the cursor from one row to another, staying in the same
+    /// column
+    fn vertical_bytes(&self, from: u16, to: u16) -> io::Result<Option<Vec<u8>>> {
+        if from == to {
+            return Ok(Some(Vec::new()));
+        }
+        // cuu and cud stop at the margins of the scroll region, so they can't cross them
+        let blocked = match self.scroll_region {
+            Some((top, _)) if to < from => from >= top && to < top,
+            Some((_, bottom)) => from <= bottom && to > bottom,
+            None => false,
+        };
+        let relative = if blocked {
+            None
+        } else if to < from {
+            self.motion_bytes("cuu", from - to)?
+        } else {
+            self.motion_bytes("cud", to - from)?
+        };
+        Ok(cheapest(relative, self.axis_bytes("vpa", to, 'd')?))
+    }
+
+    /// The ways of moving the cursor from one column to another in the same row, each with
+    /// whether it reprints text
+    fn horizontal_routes(&self, from: u16, to: u16, reprint: Option<&Vec<u8>>)
+        -> io::Result<Vec<(Vec<u8>, bool)>>
+    {
+        let mut routes = Vec::new();
+        if from == to {
+            routes.push((Vec::new(), false));
+            return Ok(routes);
+        }
+        let relative = if to < from {
+            self.motion_bytes("cub", from - to)?
+        } else {
+            self.motion_bytes("cuf", to - from)?
+        };
+        routes.extend(relative.map(|seq| (seq, false)));
+        routes.extend(self.axis_bytes("hpa", to, 'G')?.map(|seq| (seq, false)));
+        if let Some(text) = reprint {
+            routes.push((text.clone(), true));
+        }
+        Ok(routes)
+    }
+
+    /// Work out the cheapest way to move the cursor from `from` to `to`, by counting bytes like
+    /// curses' `mvcur` does. Returns the sequence and whether it reprints text, or `None` if
+    /// the terminal can't make the move.
+    fn plan_move(&self, from: Position, to: Position, reprint: &Reprint)
+        -> io::Result<Option<(Vec<u8>, bool)>>
+    {
+        let mut candidates = Vec::new();
+        let params = [Param::Number(to.row as i32), Param::Number(to.column as i32)];
+        if let Some(cup) = self.cap("cup", &params)? {
+            candidates.push((cup, false));
+        }
+        // relative to where the cursor is
+        if let Some(vertical) = self.vertical_bytes(from.row, to.row)? {
+            let reprint = reprint.from_column.as_ref();
+            for (horizontal, prints) in self.horizontal_routes(from.column, to.column, reprint)? {
+                candidates.push(([&vertical[..], &horizontal[..]].concat(), prints));
+            }
+        }
+        // relative to the start of the line, or the top left corner
+        let cr = self.cap("cr", &[])?.unwrap_or_else(|| b"\r".to_vec());
+        let home = self.cap("home", &[])?.map(|home| (home, 0));
+        for (start, row) in Some((cr, from.row)).into_iter().chain(home) {
+            if let Some(vertical) = self.vertical_bytes(row, to.row)? {
+                let reprint = reprint.from_start.as_ref();
+                for (horizontal, prints) in self.horizontal_routes(0, to.column, reprint)? {
+                    let seq = [&start[..], &vertical[..], &horizontal[..]].concat();
+                    candidates.push((seq, prints));
+                }
+            }
+        }
+        // ties go to the earliest, so absolute positioning is preferred
+        Ok(candidates.into_iter().min_by_key(|&(ref seq, _)| seq.len()))
+    }
+
+    /// Move the cursor to a column or row using the capability (`hpa` or `vpa`), or the given
+    /// ECMA-48 sequence
+    fn apply_axis(&mut self, cap: &str, n: u16, ansi_final: char) -> Result<()> {
+        if !self.apply_cap(cap, &[Param::Number(n as i32)])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::AxisPosition));
+            }
+            write!(self.out, "\x1b[{}{}", n + 1, ansi_final)?;
+        }
+        Ok(())
+    }
+
+    /// Move the cursor without giving a full position
+    pub fn move_cursor(&mut self, motion: Motion) -> Result<()> {
+        match motion {
+            Motion::Up(n) => self.apply_motion("cuu", n),
+            Motion::Down(n) => self.apply_motion("cud", n),
+            Motion::Left(n) => self.apply_motion("cub", n),
+            Motion::Right(n) => self.apply_motion("cuf", n),
+            Motion::NextLine(n) => {
+                self.carriage_return()?;
+                self.apply_motion("cud", n)
+            }
+            Motion::PrevLine(n) => {
+                self.carriage_return()?;
+                self.apply_motion("cuu", n)
+            }
+            Motion::Column(n) => self.apply_axis("hpa", n, 'G'),
+            Motion::Row(n) => self.apply_axis("vpa", n, 'd'),
+            Motion::TabForward(n) => {
+                let tab = self.cap("ht", &[])?.unwrap_or_else(|| b"\t".to_vec());
+                for _ in 0..n {
+                    self.out.write_all(&tab)?;
+                }
+                Ok(())
+            }
+            Motion::TabBack(n) => {
+                if n == 0 {
+                    return Ok(());
+                }
+                match self.cap("cbt", &[])? {
+                    Some(cbt) => for _ in 0..n {
+                        self.out.write_all(&cbt)?;
+                    },
+                    None if self.ansi => write!(self.out, "\x1b[{}Z", n)?,
+                    None => bail!(ErrorKind::NotSupported(Capability::TabStops)),
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Change the tab stops (`hts` and `tbc`, or HTS and TBC)
+    pub fn tab_stop(&mut self, change: TabStop) -> Result<()> {
+        let applied = match change {
+            TabStop::Set => self.apply_cap("hts", &[])?,
+            TabStop::ClearAll => self.apply_cap("tbc", &[])?,
+            // there's no capability to clear a single tab stop
+            TabStop::Clear => false,
+        };
+        if !applied {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::TabStops));
+            }
+            self.out.write_all(match change {
+                TabStop::Set => b"\x1bH",
+                TabStop::Clear => b"\x1b[0g",
+                TabStop::ClearAll => b"\x1b[3g",
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Save the cursor position (DECSC)
+    pub fn save_cursor(&mut self) -> Result<()> {
+        if !self.apply_cap("sc", &[])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::SaveCursor));
+            }
+            self.out.write_all(b"\x1b7")?;
+        }
+        Ok(())
+    }
+
+    /// Move the cursor back to the saved position (DECRC)
+    pub fn restore_cursor(&mut self) -> Result<()> {
+        if !self.apply_cap("rc", &[])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::SaveCursor));
+            }
+            self.out.write_all(b"\x1b8")?;
+        }
+        // DECRC puts back the attributes that were saved with the cursor
+        self.current = None;
+        Ok(())
+    }
+
+    /// Show or hide the cursor (`cnorm`/`civis`, or DECTCEM)
+    pub fn set_cursor_visible(&mut self, visible: bool) -> Result<()> {
+        if !self.apply_cap(if visible { "cnorm" } else { "civis" }, &[])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::CursorVisibility));
+            }
+            self.out.write_all(if visible { b"\x1b[?25h" } else { b"\x1b[?25l" })?;
+        }
+        Ok(())
+    }
+
+    /// Set the shape of the cursor (`Ss`/`Se`, or DECSCUSR)
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        let param = match shape {
+            CursorShape::Default => 0,
+            CursorShape::BlinkingBlock => 1,
+            CursorShape::SteadyBlock => 2,
+            CursorShape::BlinkingUnderline => 3,
+            CursorShape::SteadyUnderline => 4,
+            CursorShape::BlinkingBar => 5,
+            CursorShape::SteadyBar => 6,
+        };
+        let applied = if shape == CursorShape::Default {
+            self.apply_cap("Se", &[])?
+        } else {
+            self.apply_cap("Ss", &[Param::Number(param)])?
+        };
+        if !applied {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::CursorShape));
+            }
+            write!(self.out, "\x1b[{} q", param)?;
+        }
+        Ok(())
+    }
+
+    /// Set the color of the cursor (`Cs`/`Cr`, or OSC 12/112), or reset it to the default
+    pub fn set_cursor_color(&mut self, color: Option<Color>) -> Result<()> {
+        let applied = match color {
+            Some(color) => self.apply_cap("Cs", &[Param::Words(color_spec(color).to_owned())])?,
+            None => self.apply_cap("Cr", &[])?,
+        };
+        if !applied {
+            if !self.has_capability(Capability::CursorColor) {
+                bail!(ErrorKind::NotSupported(Capability::CursorColor));
+            }
+            match color {
+                Some(color) => write!(self.out, "\x1b]12;{}\x1b\\", color_spec(color))?,
+                None => self.out.write_all(b"\x1b]112\x1b\\")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the title and icon name (OSC 0, or the status line capabilities `tsl` and `fsl`), or
+    /// just the icon name (OSC 1)
+    fn apply_title(&mut self, title: &str, icon_only: bool) -> Result<()> {
+        // Control characters would let the title escape from the OSC sequence
+        if title.chars().any(char::is_control) {
+            bail!("title contains control characters");
+        }
+        if !icon_only && self.has_cap("tsl") && self.has_cap("fsl") {
+            self.apply_cap("tsl", &[Param::Number(0)])?;
+            self.out.write_all(title.as_bytes())?;
+            self.apply_cap("fsl", &[])?;
+        } else if self.ansi && detect::osc() {
+            write!(self.out, "\x1b]{};{}\x1b\\", if icon_only { 1 } else { 0 }, title)?;
+        } else {
+            bail!(ErrorKind::NotSupported(Capability::Title));
+        }
+        Ok(())
+    }
+
+    /// Set the title of the window, and its icon name
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        self.apply_title(title, false)
+    }
+
+    /// Set the icon name of the window
+    pub fn set_icon_name(&mut self, name: &str) -> Result<()> {
+        self.apply_title(name, true)
+    }
+
+    /// Save the title and icon name on the terminal's stack (XTWINOPS 22). Terminals without a
+    /// stack ignore this.
+    pub fn push_title(&mut self) -> Result<()> {
+        if self.ansi && detect::osc() {
+            self.out.write_all(b"\x1b[22;0t")?;
+        }
+        Ok(())
+    }
+
+    /// Restore the title and icon name from the terminal's stack (XTWINOPS 23)
+    pub fn pop_title(&mut self) -> Result<()> {
+        if self.ansi && detect::osc() {
+            self.out.write_all(b"\x1b[23;0t")?;
+        }
+        Ok(())
+    }
+
+    /// Ring the bell
+    pub fn bell(&mut self) -> Result<()> {
+        if !self.apply_cap("bel", &[])? {
+            self.out.write_all(b"\x07")?;
+        }
+        Ok(())
+    }
+
+    /// Flash the screen
+    pub fn visual_bell(&mut self) -> Result<()> {
+        let flash = match self.padded_cap("flash", &[])? {
+            Some(flash) => flash,
+            None => bail!(ErrorKind::NotSupported(Capability::VisualBell)),
+        };
+        // the padding is how long the screen stays flashed, usually between turning reverse
+        // video on and off again
+        for (text, delay) in split_padding(&flash) {
+            self.out.write_all(text)?;
+            if delay > 0 {
+                self.out.flush()?;
+                thread::sleep(Duration::from_millis(delay));
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a desktop notification, with whichever OSC sequence the terminal emulator
+    /// understands
+    pub fn notify(&mut self, title: &str, body: &str) -> Result<()> {
+        // Control characters would let the text escape from the OSC sequence
+        if title.chars().chain(body.chars()).any(char::is_control) {
+            bail!("notification contains control characters");
+        }
+        match self.notifications {
+            Some(detect::Notifications::Osc9) => if title.is_empty() {
+                write!(self.out, "\x1b]9;{}\x1b\\", body)?
+            } else {
+                write!(self.out, "\x1b]9;{}: {}\x1b\\", title, body)?
+            },
+            Some(detect::Notifications::Osc777) => {
+                // the title ends at the first `;`
+                let title = title.replace(';', ",");
+                write!(self.out, "\x1b]777;notify;{};{}\x1b\\", title, body)?
+            }
+            Some(detect::Notifications::Osc99) => {
+                // the title and body are sent separately, the notification is shown after the
+                // last part (d=1)
+                write!(self.out, "\x1b]99;i=1:d=0;{}\x1b\\", title)?;
+                write!(self.out, "\x1b]99;i=1:d=1:p=body;{}\x1b\\", body)?;
+            }
+            None => bail!(ErrorKind::NotSupported(Capability::Notification)),
+        }
+        Ok(())
+    }
+
+    /// Switch to or from the alternate screen, with `smcup` and `rmcup` or DECSET 1049 (which
+    /// also saves and restores the cursor)
+    pub fn set_alternate_screen(&mut self, on: bool) -> Result<()> {
+        let (cap, ansi): (&str, &[u8]) = if on {
+            ("smcup", b"\x1b[?1049h")
+        } else {
+            ("rmcup", b"\x1b[?1049l")
+        };
+        if !self.apply_cap(cap, &[])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::AlternateScreen));
+            }
+            self.out.write_all(ansi)?;
+        }
+        if !on {
+            // leaving restores the cursor, and with it the attributes saved on the way in
+            self.current = None;
+        }
+        Ok(())
+    }
+
+    /// Limit scrolling to a range of rows (DECSTBM), or the whole screen
+    pub fn set_scroll_region(&mut self, region: Option<(u16, u16)>) -> Result<()> {
+        let (top, bottom) = match region {
+            Some(region) => region,
+            None if self.ansi => {
+                self.out.write_all(b"\x1b[r")?;
+                self.scroll_region = None;
+                return Ok(());
+            }
+            None => (0, unix::window_size()?.ws_row.saturating_sub(1)),
+        };
+        if !self.apply_cap("csr", &[Param::Number(top as i32), Param::Number(bottom as i32)])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::ScrollRegion));
+            }
+            write!(self.out, "\x1b[{};{}r", top + 1, bottom + 1)?;
+        }
+        self.scroll_region = region;
+        Ok(())
+    }
+
+    /// Scroll using the parameterised capability (`indn` or `rin`), or the given ECMA-48
+    /// sequence
+    fn apply_scroll(&mut self, cap: &str, n: u16, ansi_final: char) -> Result<()> {
+        if n == 0 {
+            return Ok(());
+        }
+        // new lines take the background color
+        self.apply_attrs()?;
+        if !self.apply_cap(cap, &[Param::Number(n as i32)])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::Scroll));
+            }
+            write!(self.out, "\x1b[{}{}", n, ansi_final)?;
+        }
+        Ok(())
+    }
+
+    /// Scroll the text up, without moving the cursor (SU)
+    pub fn scroll_up(&mut self, n: u16) -> Result<()> {
+        self.apply_scroll("indn", n, 'S')
+    }
+
+    /// Scroll the text down, without moving the cursor (SD)
+    pub fn scroll_down(&mut self, n: u16) -> Result<()> {
+        self.apply_scroll("rin", n, 'T')
+    }
+
+    /// Move the cursor up, scrolling down at the top of the scroll region (RI)
+    pub fn reverse_index(&mut self) -> Result<()> {
+        self.apply_attrs()?;
+        if !self.apply_cap("ri", &[])? {
+            if !self.ansi {
+                bail!(ErrorKind::NotSupported(Capability::Scroll));
+            }
+            self.out.write_all(b"\x1bM")?;
+        }
+        Ok(())
+    }
+
+    /// Insert or delete lines or characters, or erase characters, using the parameterised
+    /// capability, repeating the single step version, or the ECMA-48 sequence
+    pub fn edit(&mut self, edit: Edit) -> Result<()> {
+        let (cap, n, ansi_final) = match edit {
+            Edit::InsertLines(n) => ("il", n, 'L'),
+            Edit::DeleteLines(n) => ("dl", n, 'M'),
+            Edit::InsertChars(n) => ("ich", n, '@'),
+            Edit::DeleteChars(n) => ("dch", n, 'P'),
+            Edit::EraseChars(n) => ("ech", n, 'X'),
+        };
+        if n == 0 {
+            return Ok(());
+        }
+        // the blanks take the background color
+        self.apply_attrs()?;
+        match self.motion_bytes(cap, n)? {
+            Some(seq) => self.out.write_all(&seq)?,
+            None if self.ansi => write!(self.out, "\x1b[{}{}", n, ansi_final)?,
+            None => bail!(ErrorKind::NotSupported(edit.into())),
+        }
+        Ok(())
+    }
+
+    /// Clear part of the screen, using the terminfo capabilities if the terminal has them all,
+    /// or the ECMA-48 sequence
+    pub fn clear(&mut self, clear: Clear) -> Result<()> {
+        let (caps, ansi): (&[&str], &[u8]) = match clear {
+            Clear::Screen => (&["clear"], b"\x1b[H\x1b[2J"),
+            Clear::ToEndOfScreen => (&["ed"], b"\x1b[J"),
+            Clear::ToStartOfScreen => (&[], b"\x1b[1J"),
+            Clear::Line => (&["el1", "el"], b"\x1b[2K"),
+            Clear::ToEndOfLine => (&["el"], b"\x1b[K"),
+            Clear::ToStartOfLine => (&["el1"], b"\x1b[1K"),
+            Clear::Scrollback => (&["E3"], b"\x1b[3J"),
+        };
+        // the cleared area takes the background color
+        self.apply_attrs()?;
+        if !caps.is_empty() && caps.iter().all(|cap| self.has_cap(cap)) {
+            for cap in caps {
+                self.apply_cap(cap, &[])?;
+            }
+        } else if self.ansi {
+            self.out.write_all(ansi)?;
+        } else {
+            bail!(ErrorKind::NotSupported(clear.into()));
+        }
+        Ok(())
+    }
+
+    /// Turn wrapping at the end of the line on or off (DECAWM)
+    pub fn set_autowrap(&mut self, on: bool) -> Result<()> {
+        if self.apply_cap(if on { "smam" } else { "rmam" }, &[])? {
+            Ok(())
+        } else if self.ansi {
+            self.out.write_all(if on { b"\x1b[?7h" } else { b"\x1b[?7l" })?;
+            Ok(())
+        } else {
+            bail!(ErrorKind::NotSupported(Capability::Autowrap))
+        }
+    }
+
+    /// Turn insert mode (IRM) on or off
+    pub fn set_insert_mode(&mut self, on: bool) -> Result<()> {
+        if self.apply_cap(if on { "smir" } else { "rmir" }, &[])? {
+            Ok(())
+        } else if self.ansi {
+            self.out.write_all(if on { b"\x1b[4h" } else { b"\x1b[4l" })?;
+            Ok(())
+        } else {
+            bail!(ErrorKind::NotSupported(Capability::InsertMode))
+        }
+    }
+
+    /// Return to the beginning of the current line
+    pub fn carriage_return(&mut self) -> Result<()> {
+        if !self.apply_cap("cr", &[])? {
+            self.out.write_all(b"\r")?;
+        }
+        Ok(())
+    }
+
+    /// Set how long to wait for the terminal to reply to a query
+    pub fn set_query_timeout(&mut self, timeout: Duration) {
+        self.query_timeout = timeout;
+    }
+
+    /// The sequences the terminal's keys send, from the `k*` capabilities
+    pub fn keys(&self) -> Vec<(Vec<u8>, KeyCode)> {
+        let names = [
+            ("kcuu1", KeyCode::Up), ("kcud1", KeyCode::Down), ("kcub1", KeyCode::Left),
+            ("kcuf1", KeyCode::Right), ("khome", KeyCode::Home), ("kend", KeyCode::End),
+            ("kpp", KeyCode::PageUp), ("knp", KeyCode::PageDown), ("kich1", KeyCode::Insert),
+            ("kdch1", KeyCode::Delete), ("kbs", KeyCode::Backspace), ("kcbt", KeyCode::BackTab),
+        ];
+        let function_keys = (1..25).map(|n| (format!("kf{}", n), KeyCode::F(n)));
+        names.iter()
+            .map(|&(name, code)| (name.to_string(), code))
+            .chain(function_keys)
+            .filter_map(|(name, code)| {
+                self.info.strings.get(name.as_str()).map(|seq| (seq.clone(), code))
+            })
+            .collect()
+    }
+
+    /// Take the input that arrived while waiting for replies to queries
+    pub fn take_pending_input(&mut self) -> Vec<u8> {
+        ::std::mem::replace(&mut self.pending_input, Vec::new())
+    }
+
+    /// Get the position of the cursor, by asking the terminal for a cursor position report
+    /// (DSR 6)
+    pub fn position(&mut self) -> Result<Position> {
+        if !self.ansi {
+            bail!(ErrorKind::NotSupported(Capability::Position));
+        }
+        // the terminal needs to have seen everything we've written for the position to be right
+        self.out.flush()?;
+        let reply = unix::query(b"\x1b[6n", self.query_timeout, &mut self.pending_input, |csi| {
+            match (csi.private, &csi.params[..], csi.final_byte) {
+                (None, &[row, column], b'R') => Some(Position {
+                    row: row.saturating_sub(1),
+                    column: column.saturating_sub(1),
+                }),
+                _ => None,
+            }
+        })?;
+        match reply {
+            Some(pos) => Ok(pos),
+            None => bail!(ErrorKind::NoResponse),
+        }
+    }
+
+    /// Set the position of the cursor
+    pub fn set_position(&mut self, pos: Position) -> Result<()> {
+        let params = [Param::Number(pos.row as i32), Param::Number(pos.column as i32)];
+        if self.apply_cap("cup", &params)? {
+            Ok(())
+        } else {
+            bail!(ErrorKind::NotSupported(Capability::Position))
+        }
+    }
+
+    /// Move the cursor from `from` to `to` with the fewest bytes. Returns `false` if the
+    /// terminal has no way of doing so.
+    pub fn move_cursor_from(&mut self, from: Position, to: Position, reprint: &Reprint)
+        -> Result<bool>
+    {
+        match self.plan_move(from, to, reprint)? {
+            Some((seq, prints)) => {
+                // reprinted text has to look the same as what's already there
+                if prints {
+                    self.apply_attrs()?;
+                }
+                self.out.write_all(&seq)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Whether the terminal supports synchronized output, from the `Sync` extended capability or
+    /// else by asking the terminal whether it knows mode 2026 (DECRQM). The answer is kept, so
+    /// the terminal is only asked once.
+    fn detect_synchronized_output(&mut self) -> Result<bool> {
+        if self.has_cap("Sync") {
+            return Ok(true);
+        }
+        if let Some(supported) = self.synchronized_output {
+            return Ok(supported);
+        }
+        let supported = self.ansi && {
+            self.out.flush()?;
+            let reply = unix::query(b"\x1b[?2026$p", self.query_timeout, &mut self.pending_input,
+                |csi| match (csi.private, &csi.params[..], &csi.intermediates[..], csi.final_byte) {
+                    (Some(b'?'), &[2026, mode], b"$", b'y') => Some(mode),
+                    _ => None,
+                });
+            // 0 means the mode isn't recognised and 4 that it's permanently off. Without a tty
+            // to ask there's no way to tell, so leave it off.
+            match reply {
+                Ok(Some(mode)) => mode >= 1 && mode <= 3,
+                Ok(None) | Err(_) => false,
+            }
+        };
+        self.synchronized_output = Some(supported);
+        Ok(supported)
+    }
+
+    /// Start a synchronized update (DEC mode 2026), so the terminal holds back drawing until
+    /// it ends. Does nothing if the terminal doesn't support it.
+    pub fn begin_synchronized_update(&mut self) -> Result<()> {
+        if !self.detect_synchronized_output()? {
+            return Ok(());
+        }
+        if !self.apply_cap("Sync", &[Param::Number(1)])? {
+            self.out.write_all(b"\x1b[?2026h")?;
+        }
+        self.in_synchronized_update = true;
+        Ok(())
+    }
+
+    /// End the current synchronized update, if there is one
+    pub fn end_synchronized_update(&mut self) -> Result<()> {
+        if self.in_synchronized_update {
+            if !self.apply_cap("Sync", &[Param::Number(2)])? {
+                self.out.write_all(b"\x1b[?2026l")?;
+            }
+            self.in_synchronized_update = false;
+        }
+        Ok(())
+    }
+
+    /// Start a hyperlink (OSC 8). Does nothing if the terminal doesn't support hyperlinks, so
+    /// the text is written as normal.
+    pub fn begin_hyperlink(&mut self, url: &str, id: Option<&str>) -> Result<()> {
+        // Control characters would let the url escape from the OSC sequence
+        if url.chars().any(char::is_control) {
+            bail!("hyperlink url contains control characters");
+        }
+        if let Some(id) = id {
+            if id.chars().any(|c| c.is_control() || c == ':' || c == ';') {
+                bail!("hyperlink id contains invalid characters");
+            }
+        }
+        if !self.hyperlinks {
+            return Ok(());
+        }
+        match id {
+            Some(id) => write!(self.out, "\x1b]8;id={};{}\x1b\\", id, url)?,
+            None => write!(self.out, "\x1b]8;;{}\x1b\\", url)?,
+        }
+        self.in_hyperlink = true;
+        Ok(())
+    }
+
+    /// End the current hyperlink, if there is one
+    pub fn end_hyperlink(&mut self) -> Result<()> {
+        if self.in_hyperlink {
+            self.out.write_all(b"\x1b]8;;\x1b\\")?;
+            self.in_hyperlink = false;
+        }
+        Ok(())
+    }
+
+    /// Get the dimensions of the terminal, falling back to the terminfo entry if the tty
+    /// can't tell us
+    pub fn dimensions(&self) -> Result<Dimensions> {
+        match unix::window_size() {
+            Ok(size) => Ok(Dimensions {
+                rows: size.ws_row,
+                columns: size.ws_col,
+            }),
+            Err(e) => match (self.info.numbers.get("lines"), self.info.numbers.get("cols")) {
+                (Some(&rows), Some(&columns)) => Ok(Dimensions {
+                    rows: rows as u16,
+                    columns: columns as u16,
+                }),
+                _ => Err(e.into()),
+            }
+        }
+    }
+
+    /// Ask the terminal for a size (XTWINOPS), which it reports as `CSI kind ; height ; width t`
+    fn query_size(&mut self, query: &[u8], kind: u16) -> Result<Option<(u16, u16)>> {
+        let reply = unix::query(query, self.query_timeout, &mut self.pending_input, |csi| {
+            match (csi.private, &csi.params[..], csi.final_byte) {
+                (None, &[k, height, width], b't') if k == kind => Some((width, height)),
+                _ => None,
+            }
+        })?;
+        Ok(reply)
+    }
+
+    /// Get the size of the window and its cells in pixels, from the tty if it knows, or else by
+    /// asking the terminal for the size of the text area (XTWINOPS 14) or of a cell (16). If the
+    /// terminal doesn't answer it isn't asked again.
+    pub fn pixel_dimensions(&mut self) -> Result<PixelDimensions> {
+        let dimensions = self.dimensions()?;
+        if let Ok(size) = unix::window_size() {
+            if size.ws_xpixel != 0 && size.ws_ypixel != 0 {
+                return Ok(PixelDimensions::from_window(size.ws_xpixel, size.ws_ypixel,
+                                                       dimensions));
+            }
+        }
+        if !self.ansi || self.pixel_query == Some(false) {
+            bail!(ErrorKind::NotSupported(Capability::PixelDimensions));
+        }
+        self.out.flush()?;
+        let pixels = if let Some((width, height)) = self.query_size(b"\x1b[14t", 4)? {
+            PixelDimensions::from_window(width, height, dimensions)
+        } else if let Some((width, height)) = self.query_size(b"\x1b[16t", 6)? {
+            PixelDimensions::from_cell(width, height, dimensions)
+        } else {
+            self.pixel_query = Some(false);
+            bail!(ErrorKind::NoResponse)
+        };
+        self.pixel_query = Some(true);
+        Ok(pixels)
+    }
+
+    /// Ask the terminal to resize its window (XTWINOPS 8)
+    pub fn resize_window(&mut self, dimensions: Dimensions) -> Result<()> {
+        if !self.has_capability(Capability::ResizeWindow) {
+            bail!(ErrorKind::NotSupported(Capability::ResizeWindow));
+        }
+        write!(self.out, "\x1b[8;{};{}t", dimensions.rows, dimensions.columns)?;
+        Ok(())
+    }
+
+    /// Get the inner Writer as an immutable reference
+    pub fn get_ref<'a>(&'a self) -> &'a T {
+        &self.out
+    }
+
+    /// Get the inner Writer as a mutable reference
+    pub fn get_mut<'a>(&'a mut self) -> &'a mut T {
+        &mut self.out
+    }
+
+    /// Destroy the TerminfoTerminal, returning the contained Writer
+    pub fn into_inner(self) -> T
+        where Self: Sized
+    {
+        self.out
+    }
+}
+
+impl<T: Write> Write for TerminfoTerminal<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            self.apply_attrs()?;
+        }
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_padding;
+
+    #[test]
+    fn padding() {
+        assert_eq!(split_padding(b"\x1b[?5h$<100/>\x1b[?5l"),
+                   vec![(&b"\x1b[?5h"[..], 100), (&b"\x1b[?5l"[..], 0)]);
+        assert_eq!(split_padding(b"\x1b[H\x1b[2J$<50*>"),
+                   vec![(&b"\x1b[H\x1b[2J"[..], 50), (&b""[..], 0)]);
+        // not padding at all
+        assert_eq!(split_padding(b"$<x>$5"), vec![(&b"$<x>$5"[..], 0)]);
+    }
+}
@@ -0,0 +1,375 @@
+//! Unix-specific helpers for talking to the tty directly
+
+extern crate libc;
+
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io;
+use std::mem;
+use std::ops::Range;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+use std::slice;
+use std::time::{Duration, Instant};
+
+use super::InputMode;
+
+/// Open the controlling terminal of this process for reading and writing
+pub fn open_tty() -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).open("/dev/tty")
+}
+
+/// Whether the file descriptor is a terminal
+pub fn is_tty(fd: RawFd) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+/// Get the window size of the terminal, trying the standard streams before falling back to the
+/// controlling terminal
+pub fn window_size() -> io::Result<libc::winsize> {
+    for &fd in &[libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO] {
+        if let Ok(size) = fd_window_size(fd) {
+            return Ok(size);
+        }
+    }
+    let tty = open_tty()?;
+    fd_window_size(tty.as_raw_fd())
+}
+
+fn fd_window_size(fd: libc::c_int) -> io::Result<libc::winsize> {
+    unsafe {
+        let mut size: libc::winsize = mem::zeroed();
+        if libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if size.ws_row == 0 || size.ws_col == 0 {
+            return Err(io::Error::new(io::ErrorKind::Other, "terminal reported a zero size"));
+        }
+        Ok(size)
+    }
+}
+
+/// Get the current settings of a tty
+fn get_termios(fd: RawFd) -> io::Result<libc::termios> {
+    unsafe {
+        let mut termios: libc::termios = mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(termios)
+    }
+}
+
+/// Change the settings of a tty
+fn set_termios(fd: RawFd, termios: &libc::termios) -> io::Result<()> {
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Restores the original settings of a tty when dropped (including when unwinding from a panic)
+pub struct TermiosGuard {
+    fd: RawFd,
+    original: libc::termios,
+}
+
+impl TermiosGuard {
+    /// Change the settings of the tty using `f`, remembering the originals. The file descriptor
+    /// must stay open for as long as the guard is alive.
+    pub fn new<F>(fd: RawFd, f: F) -> io::Result<TermiosGuard>
+        where F: FnOnce(&mut libc::termios)
+    {
+        let original = get_termios(fd)?;
+        let mut termios = original;
+        f(&mut termios);
+        set_termios(fd, &termios)?;
+        Ok(TermiosGuard { fd, original })
+    }
+}
+
+impl Drop for TermiosGuard {
+    fn drop(&mut self) {
+        let _ = set_termios(self.fd, &self.original);
+    }
+}
+
+/// The settings of the tty from before `set_input_mode`, which are put back when dropped
+pub struct SavedInputMode {
+    /// Puts the settings back when dropped, before the tty is closed
+    _guard: TermiosGuard,
+    newline_returns: bool,
+    /// Keeps the tty open for as long as the guard needs it
+    _tty: Option<File>,
+}
+
+impl SavedInputMode {
+    /// Whether the tty still turns each newline written into a carriage return and a newline
+    pub fn newline_returns(&self) -> bool {
+        self.newline_returns
+    }
+}
+
+/// Change how the controlling terminal (or stdin if there isn't one) handles input, until the
+/// returned value is dropped
+pub fn set_input_mode(mode: InputMode) -> io::Result<SavedInputMode> {
+    let tty = open_tty().ok();
+    let fd = tty.as_ref().map(|tty| tty.as_raw_fd()).unwrap_or(libc::STDIN_FILENO);
+    let mut newline_returns = true;
+    let guard = TermiosGuard::new(fd, |termios| {
+        match mode {
+            InputMode::Raw => unsafe { libc::cfmakeraw(termios) },
+            InputMode::Cbreak => termios.c_lflag &= !(libc::ICANON | libc::ECHO),
+        }
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        newline_returns = termios.c_oflag & libc::OPOST != 0
+            && termios.c_oflag & libc::ONLCR != 0;
+    })?;
+    Ok(SavedInputMode { _guard: guard, newline_returns, _tty: tty })
+}
+
+/// Overwrite a buffer with zeros in a way the optimizer won't remove
+fn zero(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+}
+
+/// Read a line from the controlling terminal (or stdin if there isn't one) with echo turned off
+///
+/// The buffer is grown by hand so that no copies of the password are left behind in freed
+/// memory.
+pub fn read_password() -> io::Result<String> {
+    let tty = open_tty().ok();
+    let fd = tty.as_ref().map(|tty| tty.as_raw_fd()).unwrap_or(libc::STDIN_FILENO);
+    let _guard = TermiosGuard::new(fd, |termios| termios.c_lflag &= !libc::ECHO)?;
+
+    let mut buf: Vec<u8> = Vec::with_capacity(64);
+    loop {
+        let mut byte = 0u8;
+        let read = unsafe { libc::read(fd, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if read < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            zero(&mut buf);
+            return Err(err);
+        }
+        if read == 0 || byte == b'\n' {
+            break;
+        }
+        if buf.len() == buf.capacity() {
+            let mut bigger = Vec::with_capacity(buf.capacity() * 2);
+            bigger.extend_from_slice(&buf);
+            zero(&mut buf);
+            buf = bigger;
+        }
+        buf.push(byte);
+        zero(slice::from_mut(&mut byte));
+    }
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+    String::from_utf8(buf).map_err(|e| {
+        zero(&mut e.into_bytes());
+        io::Error::new(io::ErrorKind::InvalidData, "password was not valid utf-8")
+    })
+}
+
+/// A control sequence (`CSI ... final`) sent by the terminal
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Csi {
+    /// A private marker such as `?` before the parameters, if any
+    pub private: Option<u8>,
+    pub params: Vec<u16>,
+    /// Intermediate bytes such as `$` between the parameters and the final byte
+    pub intermediates: Vec<u8>,
+    pub final_byte: u8,
+}
+
+/// Find the first complete control sequence in `buf`, returning it and the range of `buf` it
+/// occupies
+pub fn find_csi(buf: &[u8]) -> Option<(Csi, Range<usize>)> {
+    let mut start = 0;
+    while let Some(offset) = buf[start..].windows(2).position(|w| w == b"\x1b[") {
+        start += offset;
+        let mut i = start + 2;
+        let private = match buf.get(i) {
+            Some(&b) if b >= 0x3c && b <= 0x3f => {
+                i += 1;
+                Some(b)
+            }
+            _ => None,
+        };
+        let mut params = Vec::new();
+        let mut param: Option<u16> = None;
+        let mut intermediates = Vec::new();
+        loop {
+            match buf.get(i) {
+                // incomplete
+                None => return None,
+                Some(&b) if b.is_ascii_digit() && intermediates.is_empty() => {
+                    let digit = (b - b'0') as u16;
+                    param = Some(param.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                }
+                Some(&b';') if intermediates.is_empty() => params.push(param.take().unwrap_or(0)),
+                Some(&b) if b >= 0x20 && b <= 0x2f => intermediates.push(b),
+                Some(&b) if b >= 0x40 && b <= 0x7e => {
+                    if let Some(param) = param {
+                        params.push(param);
+                    }
+                    let csi = Csi { private, params, intermediates, final_byte: b };
+                    return Some((csi, start..i + 1));
+                }
+                // not a well formed sequence, look for another
+                Some(_) => break,
+            }
+            i += 1;
+        }
+        start += 1;
+    }
+    None
+}
+
+/// Send a query to the terminal and wait up to `timeout` for the reply, which `parse` picks
+/// out of the input. Returns `None` if there was no reply in time.
+///
+/// The tty is put in raw mode only while waiting for the reply. Any other input that arrives
+/// in the meantime is added to `pending`.
+pub fn query<F, R>(query: &[u8], timeout: Duration, pending: &mut Vec<u8>, parse: F)
+    -> io::Result<Option<R>>
+    where F: Fn(&Csi) -> Option<R>
+{
+    let mut tty = open_tty()?;
+    let fd = tty.as_raw_fd();
+    let _guard = TermiosGuard::new(fd, |termios| {
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+    })?;
+    tty.write_all(query)?;
+    tty.flush()?;
+
+    let start = Instant::now();
+    let mut input = Vec::new();
+    let mut buf = [0; 256];
+    loop {
+        // anything before the reply we're looking for is someone else's input
+        let mut searched = 0;
+        while let Some((csi, range)) = find_csi(&input[searched..]) {
+            let range = (range.start + searched)..(range.end + searched);
+            if let Some(reply) = parse(&csi) {
+                pending.extend_from_slice(&input[..range.start]);
+                pending.extend_from_slice(&input[range.end..]);
+                return Ok(Some(reply));
+            }
+            searched = range.end;
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout || !poll_read(fd, timeout - elapsed)? {
+            pending.extend_from_slice(&input);
+            return Ok(None);
+        }
+        match tty.read(&mut buf) {
+            Ok(read) => input.extend_from_slice(&buf[..read]),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => {
+                pending.extend_from_slice(&input);
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Where keys are read from, which is kept open between reads
+pub struct Input {
+    /// The controlling terminal, or `None` to read from stdin if there isn't one
+    tty: Option<File>,
+}
+
+impl Input {
+    pub fn open() -> io::Result<Input> {
+        Ok(Input { tty: open_tty().ok() })
+    }
+
+    /// Wait up to `timeout` (or for as long as it takes) for input, and add it to `buf`.
+    /// Returns `false` if nothing arrived in time.
+    pub fn read(&mut self, timeout: Option<Duration>, buf: &mut Vec<u8>) -> io::Result<bool> {
+        let fd = self.tty.as_ref().map(|tty| tty.as_raw_fd()).unwrap_or(libc::STDIN_FILENO);
+        read_input(fd, timeout, buf)
+    }
+}
+
+fn read_input(fd: RawFd, timeout: Option<Duration>, buf: &mut Vec<u8>) -> io::Result<bool> {
+    if let Some(timeout) = timeout {
+        if !poll_read(fd, timeout)? {
+            return Ok(false);
+        }
+    }
+    let mut bytes = [0u8; 256];
+    loop {
+        let read = unsafe {
+            libc::read(fd, bytes.as_mut_ptr() as *mut libc::c_void, bytes.len())
+        };
+        if read < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the terminal was closed"));
+        }
+        buf.extend_from_slice(&bytes[..read as usize]);
+        return Ok(true);
+    }
+}
+
+/// Wait until there is something to read from `fd`, returning `false` if the timeout expires
+/// first
+pub fn poll_read(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let start = Instant::now();
+    let mut pollfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+    loop {
+        // a signal interrupts the wait, which carries on with whatever time is left
+        let left = timeout.checked_sub(start.elapsed()).unwrap_or(Duration::from_secs(0));
+        let millis = left.as_secs() * 1000 + (left.subsec_nanos() / 1_000_000) as u64;
+        match unsafe { libc::poll(&mut pollfd, 1, millis.min(i32::max_value() as u64) as i32) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Ok(false),
+            _ => return Ok(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_csi, Csi};
+
+    #[test]
+    fn csi() {
+        let (csi, range) = find_csi(b"ab\x1b[12;40Rcd").unwrap();
+        assert_eq!(csi, Csi { private: None, params: vec![12, 40], intermediates: vec![],
+                              final_byte: b'R' });
+        assert_eq!(range, 2..10);
+
+        let (csi, _) = find_csi(b"\x1b[?2026;2$y").unwrap();
+        assert_eq!(csi, Csi { private: Some(b'?'), params: vec![2026, 2],
+                              intermediates: vec![b'$'], final_byte: b'y' });
+
+        assert_eq!(find_csi(b"\x1b[12;4"), None);
+        // a lone escape followed by a real sequence
+        let (csi, range) = find_csi(b"\x1b[\x01\x1b[5n").unwrap();
+        assert_eq!(csi.final_byte, b'n');
+        assert_eq!(range, 3..7);
+    }
+}
@@ -20,11 +20,12 @@ use std::io::prelude::*;
 use std::io;
 use std::os::raw::{c_short};
 use std::ptr;
+use std::time::Duration;
 
-use super::Attr;
+use super::{Attr, Clear, Edit, InputMode, Motion, StdStream};
 use {Error, ErrorKind, Result};
-use cap::Color;
-use {Position, Dimensions};
+use cap::{Color, CursorShape};
+use {Position, Dimensions, PixelDimensions};
 
 /// A Terminal implementation which uses the Win32 Console API.
 pub struct WinConsole<T> {
@@ -35,8 +36,45 @@ pub struct WinConsole<T> {
     def_foreground: Color,
     /// Remember the original foreground/background, for reset
     def_background: Color,
+    /// The size of the cursor (the percentage of the cell it fills), for resetting its shape
+    def_cursor_size: winapi::DWORD,
+    /// The cursor position in the screen buffer, saved by `save_cursor`
+    saved_cursor: Option<winapi::COORD>,
     foreground: Color,
     background: Color,
+    /// The colors the console was last set to, so we only call into windows when they change
+    applied: (Color, Color),
+    alternate: Option<AlternateScreen>,
+    /// The rows (relative to the window) that `scroll` and `reverse_index` are limited to
+    scroll_region: Option<(u16, u16)>,
+    /// Titles saved by `push_title`
+    titles: Vec<Vec<u16>>,
+}
+
+/// A screen buffer made active by `set_alternate_screen`, and what it replaced
+struct AlternateScreen {
+    buffer: winapi::HANDLE,
+    /// The screen buffer that was active before
+    original: winapi::HANDLE,
+    /// The standard handles that were pointed at the new buffer, and what they were before
+    std_handles: Vec<(winapi::DWORD, winapi::HANDLE)>,
+}
+
+// Console handles can be used from any thread
+unsafe impl Send for AlternateScreen {}
+
+impl Drop for AlternateScreen {
+    /// Switch back to the original screen buffer, and close the handles to both
+    fn drop(&mut self) {
+        unsafe {
+            for &(std_handle, handle) in &self.std_handles {
+                kernel32::SetStdHandle(std_handle, handle);
+            }
+            kernel32::SetConsoleActiveScreenBuffer(self.original);
+            kernel32::CloseHandle(self.buffer);
+            kernel32::CloseHandle(self.original);
+        }
+    }
 }
 
 /// Convert the color enum to a windows color bitmask
@@ -111,30 +149,279 @@ fn conout() -> io::Result<winapi::HANDLE> {
     }
 }
 
+/// Whether the standard stream is a console
+pub fn is_console(stream: StdStream) -> bool {
+    let std_handle = match stream {
+        StdStream::Stdout => winapi::STD_OUTPUT_HANDLE,
+        StdStream::Stderr => winapi::STD_ERROR_HANDLE,
+    };
+    let mut mode = 0;
+    unsafe {
+        let handle = kernel32::GetStdHandle(std_handle);
+        handle != winapi::INVALID_HANDLE_VALUE && kernel32::GetConsoleMode(handle, &mut mode) != 0
+    }
+}
+
+// Get a handle to the console input buffer
+fn conin() -> io::Result<winapi::HANDLE> {
+    let name = b"CONIN$\0";
+    let handle = unsafe {
+        kernel32::CreateFileA(name.as_ptr() as *const i8,
+                              winapi::GENERIC_READ | winapi::GENERIC_WRITE,
+                              winapi::FILE_SHARE_READ,
+                              ptr::null_mut(),
+                              winapi::OPEN_EXISTING,
+                              0,
+                              ptr::null_mut())
+    };
+    if handle == winapi::INVALID_HANDLE_VALUE {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(handle)
+    }
+}
+
+/// Restores the original mode of a console handle when dropped (including when unwinding from a
+/// panic)
+pub struct ConsoleModeGuard {
+    handle: winapi::HANDLE,
+    original: winapi::DWORD,
+}
+
+impl ConsoleModeGuard {
+    /// Change the mode of the console handle using `f`, remembering the original
+    pub fn new<F>(handle: winapi::HANDLE, f: F) -> io::Result<ConsoleModeGuard>
+        where F: FnOnce(winapi::DWORD) -> winapi::DWORD
+    {
+        let mut original = 0;
+        unsafe {
+            if kernel32::GetConsoleMode(handle, &mut original) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if kernel32::SetConsoleMode(handle, f(original)) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(ConsoleModeGuard { handle, original })
+    }
+}
+
+impl Drop for ConsoleModeGuard {
+    fn drop(&mut self) {
+        unsafe {
+            kernel32::SetConsoleMode(self.handle, self.original);
+        }
+    }
+}
+
+/// The mode of the console input from before `set_input_mode`, which is put back when dropped
+pub struct SavedInputMode {
+    guard: Option<ConsoleModeGuard>,
+    handle: winapi::HANDLE,
+}
+
+// The handle is only used to put the mode back, which can be done from any thread
+unsafe impl Send for SavedInputMode {}
+
+impl SavedInputMode {
+    /// Whether the console still turns each newline written into a carriage return and a
+    /// newline, which only depends on the output mode
+    pub fn newline_returns(&self) -> bool {
+        true
+    }
+}
+
+impl Drop for SavedInputMode {
+    fn drop(&mut self) {
+        // put the mode back before closing the handle it belongs to
+        self.guard.take();
+        unsafe {
+            kernel32::CloseHandle(self.handle);
+        }
+    }
+}
+
+/// Makes the console send keys as the same escape sequences as other terminals (missing from
+/// winapi 0.2)
+const ENABLE_VIRTUAL_TERMINAL_INPUT: winapi::DWORD = 0x0200;
+
+/// Change how the console handles input, until the returned value is dropped. Keys are sent as
+/// escape sequences, so `read_input` can be decoded the same way as on unix.
+pub fn set_input_mode(mode: InputMode) -> io::Result<SavedInputMode> {
+    let handle = conin()?;
+    let guard = ConsoleModeGuard::new(handle, |console_mode| {
+        let console_mode = console_mode | ENABLE_VIRTUAL_TERMINAL_INPUT;
+        match mode {
+            InputMode::Raw => console_mode & !(winapi::ENABLE_LINE_INPUT
+                                               | winapi::ENABLE_ECHO_INPUT
+                                               | winapi::ENABLE_PROCESSED_INPUT),
+            InputMode::Cbreak => console_mode & !(winapi::ENABLE_LINE_INPUT
+                                                  | winapi::ENABLE_ECHO_INPUT),
+        }
+    });
+    match guard {
+        Ok(guard) => Ok(SavedInputMode { guard: Some(guard), handle }),
+        Err(e) => {
+            unsafe {
+                kernel32::CloseHandle(handle);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Where keys are read from, which is kept open between reads
+pub struct Input {
+    handle: winapi::HANDLE,
+}
+
+// The console input handle can be read from any thread
+unsafe impl Send for Input {}
+
+impl Input {
+    pub fn open() -> io::Result<Input> {
+        Ok(Input { handle: conin()? })
+    }
+
+    /// Wait up to `timeout` (or for as long as it takes) for input from the console and add it
+    /// to `buf` as utf-8. Returns `false` if nothing arrived in time.
+    ///
+    /// Mouse and focus events also wake the wait, so this can block for longer than `timeout`
+    /// until a key is pressed.
+    pub fn read(&mut self, timeout: Option<Duration>, buf: &mut Vec<u8>) -> io::Result<bool> {
+        read_console_input(self.handle, timeout, buf)
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        unsafe {
+            kernel32::CloseHandle(self.handle);
+        }
+    }
+}
+
+fn read_console_input(handle: winapi::HANDLE, timeout: Option<Duration>, buf: &mut Vec<u8>)
+    -> io::Result<bool>
+{
+    let millis = match timeout {
+        Some(timeout) => {
+            let millis = timeout.as_secs() * 1000 + (timeout.subsec_nanos() / 1_000_000) as u64;
+            millis.min(winapi::INFINITE as u64 - 1) as winapi::DWORD
+        }
+        None => winapi::INFINITE,
+    };
+    match unsafe { kernel32::WaitForSingleObject(handle, millis) } {
+        winapi::WAIT_OBJECT_0 => (),
+        winapi::WAIT_TIMEOUT => return Ok(false),
+        _ => return Err(io::Error::last_os_error()),
+    }
+    let mut chars = [0u16; 256];
+    let mut read = 0;
+    let ok = unsafe {
+        kernel32::ReadConsoleW(handle, chars.as_mut_ptr() as winapi::LPVOID,
+                               chars.len() as winapi::DWORD, &mut read, ptr::null_mut())
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let text = String::from_utf16_lossy(&chars[..read as usize]);
+    buf.extend_from_slice(text.as_bytes());
+    Ok(read > 0)
+}
+
+/// Overwrite a buffer with zeros in a way the optimizer won't remove
+fn zero<N: Copy + Default>(buf: &mut [N]) {
+    for n in buf.iter_mut() {
+        unsafe { ptr::write_volatile(n, N::default()) };
+    }
+}
+
+/// Read a line from the console with echo turned off
+///
+/// The buffer is grown by hand so that no copies of the password are left behind in freed
+/// memory.
+pub fn read_password() -> io::Result<String> {
+    let handle = conin()?;
+    let result = {
+        let _guard = ConsoleModeGuard::new(handle, |mode| {
+            (mode & !winapi::ENABLE_ECHO_INPUT) | winapi::ENABLE_LINE_INPUT
+        })?;
+        read_password_line(handle)
+    };
+    unsafe {
+        kernel32::CloseHandle(handle);
+    }
+    result
+}
+
+fn read_password_line(handle: winapi::HANDLE) -> io::Result<String> {
+    let mut buf: Vec<u16> = Vec::with_capacity(64);
+    loop {
+        let mut ch: u16 = 0;
+        let mut read = 0;
+        let ok = unsafe {
+            kernel32::ReadConsoleW(handle, &mut ch as *mut u16 as winapi::LPVOID, 1, &mut read,
+                                   ptr::null_mut())
+        };
+        if ok == 0 {
+            zero(&mut buf);
+            return Err(io::Error::last_os_error());
+        }
+        if read == 0 || ch == b'\n' as u16 {
+            break;
+        }
+        if ch == b'\r' as u16 {
+            continue;
+        }
+        if buf.len() == buf.capacity() {
+            let mut bigger = Vec::with_capacity(buf.capacity() * 2);
+            bigger.extend_from_slice(&buf);
+            zero(&mut buf);
+            buf = bigger;
+        }
+        buf.push(ch);
+    }
+    let password = String::from_utf16(&buf);
+    zero(&mut buf);
+    password.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "password was not valid utf-16"))
+}
+
 // This test will only pass if it is running in an actual console, probably
 #[test]
 fn test_conout() {
     assert!(conout().is_ok())
 }
 
-impl<T: Write + Send> WinConsole<T> {
+impl<T: Write> WinConsole<T> {
+    /// The console attributes for the current colors
+    fn attributes(&self) -> winapi::WORD {
+        color_to_bits(self.foreground) | color_to_bits(self.background) << 4
+    }
+
+    /// Set the console colors, if they have changed since they were last set
     fn apply(&mut self) -> io::Result<()> {
+        if self.applied == (self.foreground, self.background) {
+            return Ok(());
+        }
         let out = try!(conout());
+        // text that is already buffered was meant to be in the old colors
         let _unused = self.buf.flush();
-        let mut accum: winapi::WORD = 0;
-        accum |= color_to_bits(self.foreground);
-        accum |= color_to_bits(self.background) << 4;
         unsafe {
-            kernel32::SetConsoleTextAttribute(out, accum);
+            kernel32::SetConsoleTextAttribute(out, self.attributes());
         }
+        self.applied = (self.foreground, self.background);
         Ok(())
     }
+}
 
+impl<T: Write + Send> WinConsole<T> {
     /// Returns `Err` whenever the terminal cannot be created for some
     /// reason.
     pub fn new(out: T) -> io::Result<WinConsole<T>> {
         let fg;
         let bg;
+        let cursor_size;
         let handle = try!(conout());
         unsafe {
             let mut buffer_info = ::std::mem::uninitialized();
@@ -144,6 +431,12 @@ impl<T: Write + Send> WinConsole<T> {
             } else {
                 return Err(io::Error::last_os_error());
             }
+            let mut cursor_info: winapi::CONSOLE_CURSOR_INFO = ::std::mem::uninitialized();
+            if kernel32::GetConsoleCursorInfo(handle, &mut cursor_info) != 0 {
+                cursor_size = cursor_info.dwSize;
+            } else {
+                return Err(io::Error::last_os_error());
+            }
         }
         Ok(WinConsole {
             buf: out,
@@ -151,12 +444,21 @@ impl<T: Write + Send> WinConsole<T> {
             def_background: bg,
             foreground: fg,
             background: bg,
+            applied: (fg, bg),
+            def_cursor_size: cursor_size,
+            saved_cursor: None,
+            alternate: None,
+            scroll_region: None,
+            titles: Vec::new(),
         })
     }
 }
 
 impl<T: Write> Write for WinConsole<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            self.apply()?;
+        }
         self.buf.write(buf)
     }
 
@@ -167,17 +469,16 @@ impl<T: Write> Write for WinConsole<T> {
 
 impl<T: Write + Send> WinConsole<T> {
 
-    /// Set an attribute (only ForegroundColor and BackgroundColor are supported)
+    /// Set an attribute (only ForegroundColor and BackgroundColor are supported). The console
+    /// isn't changed until some text is written.
     pub fn set_attr(&mut self, attr: Attr) -> Result<()> {
         match attr {
             Attr::ForegroundColor(f) => {
                 self.foreground = f;
-                try!(self.apply());
                 Ok(())
             }
             Attr::BackgroundColor(b) => {
                 self.background = b;
-                try!(self.apply());
                 Ok(())
             }
             _ => bail!(ErrorKind::NotSupported(attr.into())),
@@ -190,7 +491,16 @@ impl<T: Write + Send> WinConsole<T> {
     pub fn has_capability(&self, cap: Capability) -> bool {
         match cap {
             Capability::ForegroundColor | Capability::BackgroundColor 
-            | Capability::Position | Capability::Dimensions => true,
+            | Capability::Position | Capability::Dimensions
+            | Capability::RelativeMotion | Capability::SaveCursor
+            | Capability::CursorVisibility | Capability::CursorShape
+            | Capability::AxisPosition | Capability::ClearScreen
+            | Capability::ClearLine | Capability::ClearScrollback
+            | Capability::AlternateScreen | Capability::ScrollRegion
+            | Capability::Scroll | Capability::InsertDeleteLines
+            | Capability::InsertDeleteChars | Capability::EraseChars
+            | Capability::Autowrap | Capability::Title | Capability::PixelDimensions
+            | Capability::ResizeWindow => true,
             _ => false
         }
     }
@@ -214,68 +524,510 @@ impl<T: Write + Send> WinConsole<T> {
         Ok(())
     }
 
-    /// Move the cursor up 1 line
-    pub fn cursor_up(&mut self) -> Result<()> {
+    /// Move the cursor, stopping at the edges of the window
+    pub fn move_cursor(&mut self, motion: Motion) -> Result<()> {
         let _unused = self.buf.flush();
         let handle = try!(conout());
         unsafe {
             let mut buffer_info = ::std::mem::uninitialized();
-            if kernel32::GetConsoleScreenBufferInfo(handle, &mut buffer_info) != 0 {
-                let (x, y) = (buffer_info.dwCursorPosition.X,
-                              buffer_info.dwCursorPosition.Y);
-                if y == 0 {
-                    // Even though this might want to be a CursorPositionInvalid, on Unix there
-                    // is no checking to see if the cursor is already on the first line.
-                    // I'm not sure what the ideal behavior is, but I think it'd be silly to have
-                    // cursor_up fail in this case.
-                    Ok(())
-                } else {
-                    let pos = winapi::COORD {
-                        X: x,
-                        Y: y - 1,
-                    };
-                    if kernel32::SetConsoleCursorPosition(handle, pos) != 0 {
-                        Ok(())
-                    } else {
-                        Err(io::Error::last_os_error().into())
-                    }
+            if kernel32::GetConsoleScreenBufferInfo(handle, &mut buffer_info) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let winapi::COORD { X: x, Y: y } = buffer_info.dwCursorPosition;
+            let window = buffer_info.srWindow;
+            // Like on Unix, moving past the edge isn't an error, the cursor just stops there
+            let count = |n: u16| n.min(c_short::max_value() as u16) as c_short;
+            let (x, y) = match motion {
+                Motion::Up(n) => (x, y.saturating_sub(count(n))),
+                Motion::Down(n) => (x, y.saturating_add(count(n))),
+                Motion::Left(n) => (x.saturating_sub(count(n)), y),
+                Motion::Right(n) => (x.saturating_add(count(n)), y),
+                Motion::NextLine(n) => (0, y.saturating_add(count(n))),
+                Motion::PrevLine(n) => (0, y.saturating_sub(count(n))),
+                Motion::Column(n) => (count(n), y),
+                Motion::Row(n) => (x, window.Top.saturating_add(count(n))),
+                // The console has fixed tab stops every 8 columns
+                Motion::TabForward(n) => {
+                    ((x / 8).saturating_add(count(n)).saturating_mul(8), y)
                 }
+                Motion::TabBack(n) => {
+                    ((x.saturating_add(7) / 8).saturating_sub(count(n)).saturating_mul(8), y)
+                }
+            };
+            let pos = winapi::COORD {
+                X: x.max(0).min(buffer_info.dwSize.X - 1),
+                Y: y.max(window.Top).min(window.Bottom),
+            };
+            if kernel32::SetConsoleCursorPosition(handle, pos) != 0 {
+                Ok(())
             } else {
                 Err(io::Error::last_os_error().into())
             }
         }
     }
 
-    /// Delete all text on the current line (set the text to " " with current background and 
-    /// foreground colors)
-    pub fn delete_line(&mut self) -> Result<()> {
-        let _unused = self.buf.flush();
+    /// Remember the position of the cursor, for `restore_cursor`
+    pub fn save_cursor(&mut self) -> Result<()> {
+        self.flush()?;
         let handle = try!(conout());
         unsafe {
             let mut buffer_info = ::std::mem::uninitialized();
             if kernel32::GetConsoleScreenBufferInfo(handle, &mut buffer_info) == 0 {
                 return Err(io::Error::last_os_error().into());
             }
+            self.saved_cursor = Some(buffer_info.dwCursorPosition);
+        }
+        Ok(())
+    }
+
+    /// Move the cursor back to where it was when `save_cursor` was called. Does nothing if it
+    /// was never called.
+    pub fn restore_cursor(&mut self) -> Result<()> {
+        let pos = match self.saved_cursor {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        self.flush()?;
+        let handle = try!(conout());
+        unsafe {
+            if kernel32::SetConsoleCursorPosition(handle, pos) != 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error().into())
+            }
+        }
+    }
+
+    /// Change the cursor info (its size and visibility)
+    fn update_cursor_info<F>(&mut self, f: F) -> Result<()>
+        where F: FnOnce(&mut winapi::CONSOLE_CURSOR_INFO)
+    {
+        let _unused = self.buf.flush();
+        let handle = try!(conout());
+        unsafe {
+            let mut info = ::std::mem::uninitialized();
+            if kernel32::GetConsoleCursorInfo(handle, &mut info) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            f(&mut info);
+            if kernel32::SetConsoleCursorInfo(handle, &info) != 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error().into())
+            }
+        }
+    }
+
+    /// Show or hide the cursor
+    pub fn set_cursor_visible(&mut self, visible: bool) -> Result<()> {
+        self.update_cursor_info(|info| info.bVisible = visible as winapi::BOOL)
+    }
+
+    /// Set the shape of the cursor. The console can only change how much of the cell the cursor
+    /// fills, so bars aren't supported and the cursor always blinks.
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        let size = match shape {
+            CursorShape::Default => self.def_cursor_size,
+            CursorShape::BlinkingBlock | CursorShape::SteadyBlock => 100,
+            CursorShape::BlinkingUnderline | CursorShape::SteadyUnderline => 25,
+            CursorShape::BlinkingBar | CursorShape::SteadyBar => {
+                bail!(ErrorKind::NotSupported(Capability::CursorShape))
+            }
+        };
+        self.update_cursor_info(|info| info.dwSize = size)
+    }
+
+    /// Ring the bell, by writing BEL for the console to interpret
+    pub fn bell(&mut self) -> Result<()> {
+        self.buf.write_all(b"\x07")?;
+        Ok(())
+    }
+
+    /// Get the size of the window in pixels, from the size of the console font
+    pub fn pixel_dimensions(&self) -> Result<PixelDimensions> {
+        let dimensions = self.dimensions()?;
+        let handle = conout()?;
+        unsafe {
+            let mut font: winapi::CONSOLE_FONT_INFO = ::std::mem::uninitialized();
+            if kernel32::GetCurrentConsoleFont(handle, winapi::FALSE, &mut font) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(PixelDimensions::from_cell(font.dwFontSize.X as u16, font.dwFontSize.Y as u16,
+                                          dimensions))
+        }
+    }
+
+    /// Resize the console window, growing the screen buffer first if it is too small to hold
+    /// the window
+    pub fn resize_window(&mut self, dimensions: Dimensions) -> Result<()> {
+        let handle = conout()?;
+        unsafe {
+            let mut info = ::std::mem::uninitialized();
+            if kernel32::GetConsoleScreenBufferInfo(handle, &mut info) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let rows = dimensions.rows.min(c_short::max_value() as u16) as c_short;
+            let columns = dimensions.columns.min(c_short::max_value() as u16) as c_short;
+            let size = winapi::COORD {
+                X: info.dwSize.X.max(columns),
+                Y: info.dwSize.Y.max(rows),
+            };
+            if (size.X, size.Y) != (info.dwSize.X, info.dwSize.Y)
+                && kernel32::SetConsoleScreenBufferSize(handle, size) == 0
+            {
+                return Err(io::Error::last_os_error().into());
+            }
+            // keep the top left corner where it is, unless the window would then go past the
+            // end of the buffer
+            let left = info.srWindow.Left.min(size.X - columns);
+            let top = info.srWindow.Top.min(size.Y - rows);
+            let window = winapi::SMALL_RECT {
+                Left: left,
+                Top: top,
+                Right: left + columns - 1,
+                Bottom: top + rows - 1,
+            };
+            if kernel32::SetConsoleWindowInfo(handle, winapi::TRUE, &window) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the title of the console window
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        let title: Vec<u16> = title.encode_utf16().chain(Some(0)).collect();
+        unsafe {
+            if kernel32::SetConsoleTitleW(title.as_ptr()) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Remember the title of the console window, for `pop_title`
+    pub fn push_title(&mut self) -> Result<()> {
+        // the title can be up to 64K, including the terminating null
+        let mut title = vec![0u16; 32 * 1024];
+        unsafe {
+            // an empty title can't be told apart from an error, and can be put back either way
+            let len = kernel32::GetConsoleTitleW(title.as_mut_ptr(), title.len() as winapi::DWORD);
+            title.truncate(len as usize);
+        }
+        title.push(0);
+        self.titles.push(title);
+        Ok(())
+    }
+
+    /// Set the title of the console window back to the one saved by `push_title`. Does nothing
+    /// if there isn't one.
+    pub fn pop_title(&mut self) -> Result<()> {
+        if let Some(title) = self.titles.pop() {
+            unsafe {
+                if kernel32::SetConsoleTitleW(title.as_ptr()) == 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Switch to a new screen buffer, or back to the one that was active before. The standard
+    /// output and error handles are pointed at the active buffer, so text written to them goes
+    /// there.
+    pub fn set_alternate_screen(&mut self, on: bool) -> Result<()> {
+        let _unused = self.buf.flush();
+        if !on {
+            // dropping it switches back to the original buffer
+            if let Some(alternate) = self.alternate.take() {
+                // the original buffer still has the colors it had when we left it
+                unsafe {
+                    kernel32::SetConsoleTextAttribute(alternate.original, self.attributes());
+                }
+            }
+            return Ok(());
+        }
+        if self.alternate.is_some() {
+            return Ok(());
+        }
+        let original = try!(conout());
+        unsafe {
+            let buffer = kernel32::CreateConsoleScreenBuffer(
+                winapi::GENERIC_READ | winapi::GENERIC_WRITE,
+                winapi::FILE_SHARE_READ | winapi::FILE_SHARE_WRITE,
+                ptr::null(),
+                winapi::CONSOLE_TEXTMODE_BUFFER,
+                ptr::null_mut());
+            if buffer == winapi::INVALID_HANDLE_VALUE {
+                let err = io::Error::last_os_error();
+                kernel32::CloseHandle(original);
+                return Err(err.into());
+            }
+            if kernel32::SetConsoleActiveScreenBuffer(buffer) == 0 {
+                let err = io::Error::last_os_error();
+                kernel32::CloseHandle(buffer);
+                kernel32::CloseHandle(original);
+                return Err(err.into());
+            }
+            kernel32::SetConsoleTextAttribute(buffer, self.attributes());
+            self.applied = (self.foreground, self.background);
+            let mut std_handles = Vec::new();
+            for &std_handle in &[winapi::STD_OUTPUT_HANDLE, winapi::STD_ERROR_HANDLE] {
+                let handle = kernel32::GetStdHandle(std_handle);
+                let mut mode = 0;
+                if handle != winapi::INVALID_HANDLE_VALUE
+                    && kernel32::GetConsoleMode(handle, &mut mode) != 0
+                {
+                    kernel32::SetStdHandle(std_handle, buffer);
+                    std_handles.push((std_handle, handle));
+                }
+            }
+            self.alternate = Some(AlternateScreen { buffer, original, std_handles });
+        }
+        Ok(())
+    }
+
+    /// Limit `scroll` and `reverse_index` to a range of rows, and move the cursor to the top
+    /// left like DECSTBM does. The console doesn't have scroll regions, so text written past the
+    /// bottom of the region still scrolls the whole window.
+    pub fn set_scroll_region(&mut self, region: Option<(u16, u16)>) -> Result<()> {
+        self.scroll_region = region;
+        self.set_position(Position { row: 0, column: 0 })
+    }
+
+    /// The rows (in screen buffer coordinates) of the scroll region
+    fn scroll_rows(&self, window: &winapi::SMALL_RECT) -> (c_short, c_short) {
+        match self.scroll_region {
+            Some((top, bottom)) => (window.Top + top as c_short,
+                                    (window.Top + bottom as c_short).min(window.Bottom)),
+            None => (window.Top, window.Bottom),
+        }
+    }
+
+    /// Move the text in `rect` (in screen buffer coordinates) by `dx` columns and `dy` rows,
+    /// clipped to `rect`, filling what it uncovers with spaces in the current colors
+    unsafe fn shift(&self, handle: winapi::HANDLE, rect: winapi::SMALL_RECT, dx: c_short,
+                    dy: c_short) -> Result<()> {
+        let origin = winapi::COORD { X: rect.Left + dx, Y: rect.Top + dy };
+        let fill = winapi::CHAR_INFO { UnicodeChar: 0x0020, Attributes: self.attributes() };
+        if kernel32::ScrollConsoleScreenBufferW(handle, &rect, &rect, origin, &fill) == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Scroll the text in the scroll region up `n` lines, or down if `n` is negative, filling
+    /// the lines uncovered with spaces in the current colors
+    pub fn scroll(&mut self, n: i32) -> Result<()> {
+        if n == 0 {
+            return Ok(());
+        }
+        self.apply()?;
+        let _unused = self.buf.flush();
+        let handle = try!(conout());
+        unsafe {
+            let mut buffer_info: winapi::CONSOLE_SCREEN_BUFFER_INFO = ::std::mem::uninitialized();
+            if kernel32::GetConsoleScreenBufferInfo(handle, &mut buffer_info) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let (top, bottom) = self.scroll_rows(&buffer_info.srWindow);
+            let region = winapi::SMALL_RECT {
+                Left: 0,
+                Top: top,
+                Right: buffer_info.dwSize.X - 1,
+                Bottom: bottom,
+            };
+            let height = (bottom - top + 1) as i32;
+            self.shift(handle, region, 0, -n.max(-height).min(height) as c_short)
+        }
+    }
+
+    /// Insert or delete lines or characters at the cursor, or erase characters
+    pub fn edit(&mut self, edit: Edit) -> Result<()> {
+        self.apply()?;
+        let _unused = self.buf.flush();
+        let handle = try!(conout());
+        unsafe {
+            let mut buffer_info: winapi::CONSOLE_SCREEN_BUFFER_INFO = ::std::mem::uninitialized();
+            if kernel32::GetConsoleScreenBufferInfo(handle, &mut buffer_info) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let pos = buffer_info.dwCursorPosition;
+            let right = buffer_info.dwSize.X - 1;
+            // how far the cursor is from the edge the text moves towards, so `n` can be limited
+            let (top, bottom) = self.scroll_rows(&buffer_info.srWindow);
+            let lines = bottom - pos.Y + 1;
+            let chars = right - pos.X + 1;
+            let limit = |n: u16, max: c_short| n.min(max.max(0) as u16) as c_short;
+            match edit {
+                Edit::InsertLines(n) | Edit::DeleteLines(n) => {
+                    // like on terminals, this does nothing outside the scroll region
+                    if pos.Y < top || pos.Y > bottom {
+                        return Ok(());
+                    }
+                    let rect = winapi::SMALL_RECT {
+                        Left: 0,
+                        Top: pos.Y,
+                        Right: right,
+                        Bottom: bottom,
+                    };
+                    let n = limit(n, lines);
+                    let dy = if let Edit::InsertLines(_) = edit { n } else { -n };
+                    self.shift(handle, rect, 0, dy)?;
+                    let start = winapi::COORD { X: 0, Y: pos.Y };
+                    if kernel32::SetConsoleCursorPosition(handle, start) == 0 {
+                        return Err(io::Error::last_os_error().into());
+                    }
+                }
+                Edit::InsertChars(n) | Edit::DeleteChars(n) => {
+                    let rect = winapi::SMALL_RECT {
+                        Left: pos.X,
+                        Top: pos.Y,
+                        Right: right,
+                        Bottom: pos.Y,
+                    };
+                    let n = limit(n, chars);
+                    let dx = if let Edit::InsertChars(_) = edit { n } else { -n };
+                    self.shift(handle, rect, dx, 0)?;
+                }
+                Edit::EraseChars(n) => {
+                    let len = limit(n, chars) as winapi::DWORD;
+                    let mut written = 0;
+                    if kernel32::FillConsoleOutputCharacterW(handle, 0x0020, len, pos,
+                                                             &mut written) == 0 {
+                        return Err(io::Error::last_os_error().into());
+                    }
+                    if kernel32::FillConsoleOutputAttribute(handle, self.attributes(), len, pos,
+                                                            &mut written) == 0 {
+                        return Err(io::Error::last_os_error().into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the cursor up a line, scrolling down if it is at the top of the scroll region
+    pub fn reverse_index(&mut self) -> Result<()> {
+        let pos = self.position()?;
+        let top = self.scroll_region.map(|(top, _)| top).unwrap_or(0);
+        if pos.row == top {
+            self.scroll(-1)
+        } else {
+            self.move_cursor(Motion::Up(1))
+        }
+    }
+
+    /// Clear part of the screen, filling it with spaces in the current colors
+    pub fn clear(&mut self, clear: Clear) -> Result<()> {
+        // the cleared area takes the current colors
+        self.apply()?;
+        let _unused = self.buf.flush();
+        let handle = try!(conout());
+        let attributes = self.attributes();
+        unsafe {
+            let mut buffer_info: winapi::CONSOLE_SCREEN_BUFFER_INFO = ::std::mem::uninitialized();
+            if kernel32::GetConsoleScreenBufferInfo(handle, &mut buffer_info) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
             let pos = buffer_info.dwCursorPosition;
-            let size = buffer_info.dwSize;
-            let num = (size.X - pos.X) as winapi::DWORD;
+            let window = buffer_info.srWindow;
+            let width = buffer_info.dwSize.X as winapi::DWORD;
+            let x = pos.X as winapi::DWORD;
+            // rows of the window above and below the cursor
+            let above = (pos.Y - window.Top) as winapi::DWORD;
+            let below = (window.Bottom - pos.Y) as winapi::DWORD;
+            let top = winapi::COORD { X: 0, Y: window.Top };
+            let line = winapi::COORD { X: 0, Y: pos.Y };
+            // where to start filling, and how many cells
+            let (start, len) = match clear {
+                Clear::Screen => (top, width * (above + below + 1)),
+                Clear::ToEndOfScreen => (pos, width * below + width - x),
+                Clear::ToStartOfScreen => (top, width * above + x + 1),
+                Clear::Line => (line, width),
+                Clear::ToEndOfLine => (pos, width - x),
+                Clear::ToStartOfLine => (line, x + 1),
+                Clear::Scrollback => return self.clear_scrollback(handle, &buffer_info),
+            };
             let mut written = 0;
             // 0x0020 = " " in utf-16
-            if kernel32::FillConsoleOutputCharacterW(handle, 0x0020, num, pos, &mut written) == 0 {
+            if kernel32::FillConsoleOutputCharacterW(handle, 0x0020, len, start,
+                                                     &mut written) == 0 {
                 return Err(io::Error::last_os_error().into());
             }
-            debug_assert_eq!(written, num);
-            if kernel32::FillConsoleOutputAttribute(handle, 0, num, pos, &mut written) == 0 {
+            if kernel32::FillConsoleOutputAttribute(handle, attributes, len, start,
+                                                    &mut written) == 0 {
                 return Err(io::Error::last_os_error().into());
             }
-            debug_assert_eq!(written, num);
-            // Similar reasoning for not failing as in cursor_up -- it doesn't even make
-            // sense to
-            // me that these APIs could have written 0, unless the terminal is width zero.
+            if clear == Clear::Screen {
+                let home = winapi::COORD { X: window.Left, Y: window.Top };
+                if kernel32::SetConsoleCursorPosition(handle, home) == 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
             Ok(())
         }
     }
 
+    /// Throw away the lines above the window, by moving what's in the window to the top of the
+    /// screen buffer
+    unsafe fn clear_scrollback(&mut self, handle: winapi::HANDLE,
+                               buffer_info: &winapi::CONSOLE_SCREEN_BUFFER_INFO) -> Result<()> {
+        let window = buffer_info.srWindow;
+        if window.Top == 0 {
+            return Ok(());
+        }
+        let scroll = winapi::SMALL_RECT {
+            Left: 0,
+            Top: 0,
+            Right: buffer_info.dwSize.X - 1,
+            Bottom: buffer_info.dwSize.Y - 1,
+        };
+        let fill = winapi::CHAR_INFO { UnicodeChar: 0x0020, Attributes: self.attributes() };
+        let origin = winapi::COORD { X: 0, Y: -window.Top };
+        if kernel32::ScrollConsoleScreenBufferW(handle, &scroll, ptr::null(), origin, &fill) == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let moved = winapi::SMALL_RECT {
+            Left: window.Left,
+            Top: 0,
+            Right: window.Right,
+            Bottom: window.Bottom - window.Top,
+        };
+        if kernel32::SetConsoleWindowInfo(handle, winapi::TRUE, &moved) == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let pos = buffer_info.dwCursorPosition;
+        let pos = winapi::COORD { X: pos.X, Y: pos.Y - window.Top };
+        if kernel32::SetConsoleCursorPosition(handle, pos) == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Turn wrapping at the end of the line on or off, with the console's
+    /// `ENABLE_WRAP_AT_EOL_OUTPUT` mode
+    pub fn set_autowrap(&mut self, on: bool) -> Result<()> {
+        // text that has already been written should wrap the old way
+        let _unused = self.buf.flush();
+        let handle = conout()?;
+        unsafe {
+            let mut mode = 0;
+            if kernel32::GetConsoleMode(handle, &mut mode) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let mode = if on {
+                mode | winapi::ENABLE_WRAP_AT_EOL_OUTPUT
+            } else {
+                mode & !winapi::ENABLE_WRAP_AT_EOL_OUTPUT
+            };
+            if kernel32::SetConsoleMode(handle, mode) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        Ok(())
+    }
+
     /// Return to the beginning of the current line
     pub fn carriage_return(&mut self) -> Result<()> {
         let _unused = self.buf.flush();
@@ -310,8 +1062,8 @@ impl<T: Write + Send> WinConsole<T> {
             let mut buffer_info = ::std::mem::uninitialized();
             if kernel32::GetConsoleScreenBufferInfo(handle, &mut buffer_info) != 0 {
                 Ok(Position {
-                    column: 0.max(buffer_info.dwCursorPosition.X - buffer_info.srWindow.Top) as u16,
-                    row: 0.max(buffer_info.dwCursorPosition.Y - buffer_info.srWindow.Left) as u16,
+                    column: 0.max(buffer_info.dwCursorPosition.X - buffer_info.srWindow.Left) as u16,
+                    row: 0.max(buffer_info.dwCursorPosition.Y - buffer_info.srWindow.Top) as u16,
                 })
             } else {
                 Err(io::Error::last_os_error().into())
@@ -0,0 +1,780 @@
+//! Keeping track of where the cursor is, by interpreting what we write to the terminal
+//!
+//! This is only an estimate. Anything that moves the cursor behind our back (the user typing
+//! with echo on, another process writing to the terminal, an escape sequence we don't
+//! understand) makes it wrong, so where we can't tell what happened we forget the position
+//! rather than guess.
+
+use unicode_width::UnicodeWidthChar;
+
+use sys::{Edit, Motion};
+use {Dimensions, Position};
+
+/// Where we are in an escape sequence being written
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum State {
+    /// Not in an escape sequence
+    Ground,
+    /// After `ESC`
+    Escape,
+    /// In a control sequence (`ESC [`)
+    Csi,
+    /// In an operating system command or other string (`ESC ]`), which ends with `BEL` or `ST`
+    String,
+    /// After `ESC` in a string, which is the first half of `ST`
+    StringEscape,
+}
+
+/// What we know about a cell on the screen
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Cell {
+    /// We don't know what's in the cell
+    Unknown,
+    /// A character we wrote, and the style generation it was written in
+    Char(char, u32),
+    /// The right half of a wide character
+    Wide,
+}
+
+/// The width of a tab stop, when they haven't been changed
+const TAB_WIDTH: u16 = 8;
+
+/// An estimate of the cursor position
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    row: Option<u16>,
+    column: Option<u16>,
+    dimensions: Option<Dimensions>,
+    /// The last column has been written to, so the cursor will wrap before the next character
+    pending_wrap: bool,
+    /// Whether the cursor wraps to the next line at the end of a line
+    autowrap: bool,
+    /// Whether characters written push the rest of the line right instead of replacing it
+    insert_mode: bool,
+    /// Whether the tty turns newlines into a carriage return and a newline (ONLCR)
+    newline_returns: bool,
+    /// Whether the tab stops have been changed from every 8 columns
+    custom_tabs: bool,
+    /// The rows that scrolling is limited to, if it is
+    scroll_region: Option<(u16, u16)>,
+    saved: Option<(Option<u16>, Option<u16>)>,
+    state: State,
+    /// What we've written on each row, so the cursor can be moved right by writing the same
+    /// thing again. Empty until something is written.
+    lines: Vec<Vec<Cell>>,
+    /// Changed whenever the style of text written changes, so that text is only written again
+    /// if it would look the same
+    generation: u32,
+    /// The start of a utf-8 character that was split between writes
+    partial: Vec<u8>,
+}
+
+impl Tracker {
+    pub fn new(dimensions: Option<Dimensions>) -> Tracker {
+        Tracker {
+            row: None,
+            column: None,
+            dimensions,
+            pending_wrap: false,
+            autowrap: true,
+            insert_mode: false,
+            newline_returns: true,
+            custom_tabs: false,
+            scroll_region: None,
+            saved: None,
+            state: State::Ground,
+            lines: Vec::new(),
+            generation: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// The estimated position, if we know it
+    pub fn position(&self) -> Option<Position> {
+        match (self.row, self.column) {
+            (Some(row), Some(column)) => Some(Position { row, column }),
+            _ => None,
+        }
+    }
+
+    /// The cursor has been moved to a known position
+    pub fn set_position(&mut self, pos: Position) {
+        self.row = Some(self.clamp_row(pos.row));
+        self.column = Some(self.clamp_column(pos.column));
+        self.pending_wrap = false;
+    }
+
+    /// Whether the last column has been written to, so the cursor is about to wrap. Terminals
+    /// don't agree on what relative motions do in this state.
+    pub fn pending_wrap(&self) -> bool {
+        self.pending_wrap
+    }
+
+    /// The cursor has been moved somewhere we can't tell, and the screen may have been changed
+    pub fn forget(&mut self) {
+        self.row = None;
+        self.column = None;
+        self.pending_wrap = false;
+        self.forget_contents();
+    }
+
+    /// The screen has been changed in a way we can't tell
+    pub fn forget_contents(&mut self) {
+        self.lines.clear();
+    }
+
+    /// The line has been erased from the cursor to the end
+    pub fn erase_to_end_of_line(&mut self) {
+        match (self.row, self.column) {
+            (Some(row), Some(column)) => {
+                if let Some(line) = self.lines.get_mut(row as usize) {
+                    for cell in line.iter_mut().skip(column as usize) {
+                        *cell = Cell::Unknown;
+                    }
+                }
+            }
+            _ => self.forget_contents(),
+        }
+    }
+
+    /// The style of text written from now on has changed
+    pub fn style_changed(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn autowrap(&self) -> bool {
+        self.autowrap
+    }
+
+    /// Wrapping at the end of the line has been turned on or off
+    pub fn set_autowrap(&mut self, on: bool) {
+        self.autowrap = on;
+        if !on {
+            self.pending_wrap = false;
+        }
+    }
+
+    pub fn insert_mode(&self) -> bool {
+        self.insert_mode
+    }
+
+    /// Insert mode has been turned on or off
+    pub fn set_insert_mode(&mut self, on: bool) {
+        self.insert_mode = on;
+    }
+
+    pub fn newline_returns(&self) -> bool {
+        self.newline_returns
+    }
+
+    /// The tty has started or stopped turning newlines into a carriage return and a newline
+    pub fn set_newline_returns(&mut self, on: bool) {
+        self.newline_returns = on;
+    }
+
+    /// The text that would move the cursor from `start` to `end` on `row` by writing what is
+    /// already there, if we know what that is and it was written in the current style. Never
+    /// in insert mode, where writing it again would push it along instead.
+    pub fn reprint(&self, row: u16, start: u16, end: u16) -> Option<Vec<u8>> {
+        if self.insert_mode {
+            return None;
+        }
+        let line = self.lines.get(row as usize)?;
+        let mut text = String::new();
+        let mut column = start as usize;
+        while column < end as usize {
+            match line.get(column) {
+                Some(&Cell::Char(c, generation)) if generation == self.generation => {
+                    let width = c.width().unwrap_or(0);
+                    if width == 2 && line.get(column + 1) != Some(&Cell::Wide) {
+                        return None;
+                    }
+                    text.push(c);
+                    column += width;
+                }
+                _ => return None,
+            }
+        }
+        // a wide character can't be half reprinted
+        if column == end as usize {
+            Some(text.into_bytes())
+        } else {
+            None
+        }
+    }
+
+    /// Record what has been written in a cell
+    fn set_cell(&mut self, row: u16, column: u16, cell: Cell) {
+        let (rows, columns) = match self.dimensions {
+            Some(dims) => (dims.rows as usize, dims.columns as usize),
+            None => return,
+        };
+        if self.lines.len() < rows {
+            self.lines.resize(rows, Vec::new());
+        }
+        if let Some(line) = self.lines.get_mut(row as usize) {
+            if line.len() < columns {
+                line.resize(columns, Cell::Unknown);
+            }
+            if let Some(old) = line.get_mut(column as usize) {
+                *old = cell;
+            }
+        }
+    }
+
+    /// Push the rest of the line right to make room for `n` cells, losing what goes past the
+    /// end
+    fn insert_cells(&mut self, row: u16, column: u16, n: u16) {
+        if let Some(line) = self.lines.get_mut(row as usize) {
+            let columns = line.len();
+            if (column as usize) < columns {
+                for _ in 0..n {
+                    line.insert(column as usize, Cell::Unknown);
+                }
+                line.truncate(columns);
+            }
+        }
+    }
+
+    pub fn set_dimensions(&mut self, dimensions: Dimensions) {
+        if self.dimensions != Some(dimensions) {
+            self.forget_contents();
+        }
+        self.dimensions = Some(dimensions);
+        self.row = self.row.map(|row| self.clamp_row(row));
+        self.column = self.column.map(|column| self.clamp_column(column));
+    }
+
+    /// The tab stops have been changed
+    pub fn set_custom_tabs(&mut self) {
+        self.custom_tabs = true;
+    }
+
+    /// Scrolling has been limited to some rows, which also moves the cursor to the top left
+    pub fn set_scroll_region(&mut self, region: Option<(u16, u16)>) {
+        self.scroll_region = region;
+        self.set_position(Position { row: 0, column: 0 });
+    }
+
+    /// Whether relative motions or newlines can get from row `from` to row `to`, without being
+    /// stopped by the margins of the scroll region
+    pub fn can_reach_row(&self, from: u16, to: u16) -> bool {
+        if to < from {
+            self.row_above(from, from - to) == to
+        } else {
+            self.row_below(from, to - from) == to
+        }
+    }
+
+    /// The first and last rows that scroll
+    fn scrolling_rows(&self) -> Option<(u16, u16)> {
+        match (self.scroll_region, self.dimensions) {
+            (Some(region), _) => Some(region),
+            (None, Some(dims)) => Some((0, dims.rows.saturating_sub(1))),
+            (None, None) => None,
+        }
+    }
+
+    /// The text in the scroll region has moved up `n` lines, or down if `n` is negative
+    pub fn scroll(&mut self, n: i32) {
+        match self.scrolling_rows() {
+            Some((top, bottom)) => self.scroll_rows(top as usize, bottom as usize, n),
+            None => self.forget_contents(),
+        }
+    }
+
+    /// The text in some rows has moved up `n` lines, or down if `n` is negative
+    fn scroll_rows(&mut self, top: usize, bottom: usize, n: i32) {
+        if self.lines.len() <= bottom {
+            return;
+        }
+        for _ in 0..n.abs().min((bottom - top + 1) as i32) {
+            if n > 0 {
+                self.lines.remove(top);
+                self.lines.insert(bottom, Vec::new());
+            } else {
+                self.lines.remove(bottom);
+                self.lines.insert(top, Vec::new());
+            }
+        }
+    }
+
+    /// The cursor has moved up a line, scrolling down if it was at the top of the scroll
+    /// region
+    pub fn reverse_index(&mut self) {
+        self.pending_wrap = false;
+        match (self.row, self.scrolling_rows()) {
+            (Some(row), Some((top, _))) if row == top => self.scroll(-1),
+            (Some(row), _) => self.row = Some(row.saturating_sub(1)),
+            (None, _) => self.forget_contents(),
+        }
+    }
+
+    /// Lines or characters have been inserted or deleted, or characters erased
+    pub fn edit(&mut self, edit: Edit) {
+        let (row, column) = match (self.row, self.column) {
+            (Some(row), Some(column)) => (row, column as usize),
+            _ => return self.forget_contents(),
+        };
+        match edit {
+            Edit::InsertLines(n) | Edit::DeleteLines(n) => {
+                self.column = Some(0);
+                self.pending_wrap = false;
+                match self.scrolling_rows() {
+                    Some((top, bottom)) if row >= top && row <= bottom => {
+                        let n = if let Edit::InsertLines(_) = edit { -(n as i32) } else { n as i32 };
+                        self.scroll_rows(row as usize, bottom as usize, n);
+                    }
+                    Some(_) => (),
+                    None => self.forget_contents(),
+                }
+            }
+            Edit::InsertChars(n) | Edit::DeleteChars(n) | Edit::EraseChars(n) => {
+                let line = match self.lines.get_mut(row as usize) {
+                    Some(line) if column < line.len() => line,
+                    _ => return,
+                };
+                let columns = line.len();
+                let n = (n as usize).min(columns - column);
+                match edit {
+                    Edit::InsertChars(_) => {
+                        for _ in 0..n {
+                            line.insert(column, Cell::Unknown);
+                        }
+                        line.truncate(columns);
+                    }
+                    Edit::DeleteChars(_) => {
+                        line.drain(column..column + n);
+                        line.resize(columns, Cell::Unknown);
+                    }
+                    _ => for cell in &mut line[column..column + n] {
+                        *cell = Cell::Unknown;
+                    },
+                }
+            }
+        }
+    }
+
+    pub fn save(&mut self) {
+        self.saved = Some((self.row, self.column));
+    }
+
+    pub fn restore(&mut self) {
+        if let Some((row, column)) = self.saved {
+            self.row = row;
+            self.column = column;
+            self.pending_wrap = false;
+        }
+    }
+
+    fn clamp_row(&self, row: u16) -> u16 {
+        match self.dimensions {
+            Some(dims) => row.min(dims.rows.saturating_sub(1)),
+            None => row,
+        }
+    }
+
+    fn clamp_column(&self, column: u16) -> u16 {
+        match self.dimensions {
+            Some(dims) => column.min(dims.columns.saturating_sub(1)),
+            None => column,
+        }
+    }
+
+    /// Where moving up `n` rows from `row` ends up. Relative motions stop at the top margin of
+    /// the scroll region if they start inside or below it.
+    fn row_above(&self, row: u16, n: u16) -> u16 {
+        let top = match self.scroll_region {
+            Some((top, _)) if row >= top => top,
+            _ => 0,
+        };
+        row.saturating_sub(n).max(top)
+    }
+
+    /// Where moving down `n` rows from `row` ends up. Relative motions stop at the bottom
+    /// margin of the scroll region if they start inside or above it.
+    fn row_below(&self, row: u16, n: u16) -> u16 {
+        let moved = self.clamp_row(row.saturating_add(n));
+        match self.scroll_region {
+            Some((_, bottom)) if row <= bottom => moved.min(bottom),
+            _ => moved,
+        }
+    }
+
+    /// The next tab stop after `column`, if we know where the tab stops are
+    fn next_tab(&self, column: u16) -> Option<u16> {
+        if self.custom_tabs {
+            None
+        } else {
+            Some(self.clamp_column((column / TAB_WIDTH + 1) * TAB_WIDTH))
+        }
+    }
+
+    /// The cursor has been moved (and hasn't gone past the edge of the screen)
+    pub fn motion(&mut self, motion: Motion) {
+        self.pending_wrap = false;
+        match motion {
+            Motion::Up(n) => self.row = self.row.map(|row| self.row_above(row, n)),
+            Motion::Down(n) => self.row = self.row.map(|row| self.row_below(row, n)),
+            Motion::Left(n) => self.column = self.column.map(|col| col.saturating_sub(n)),
+            Motion::Right(n) => {
+                self.column = self.column.map(|col| self.clamp_column(col.saturating_add(n)))
+            }
+            Motion::NextLine(n) => {
+                self.row = self.row.map(|row| self.row_below(row, n));
+                self.column = Some(0);
+            }
+            Motion::PrevLine(n) => {
+                self.row = self.row.map(|row| self.row_above(row, n));
+                self.column = Some(0);
+            }
+            Motion::Column(n) => self.column = Some(self.clamp_column(n)),
+            Motion::Row(n) => self.row = Some(self.clamp_row(n)),
+            Motion::TabForward(n) => for _ in 0..n {
+                self.column = self.column.and_then(|col| self.next_tab(col));
+            },
+            Motion::TabBack(n) => for _ in 0..n {
+                self.column = match self.column {
+                    Some(col) if !self.custom_tabs => {
+                        Some((col.saturating_sub(1) / TAB_WIDTH) * TAB_WIDTH)
+                    }
+                    _ => None,
+                };
+            },
+        }
+    }
+
+    /// Move down a line, scrolling if we're at the bottom of the scroll region
+    fn line_feed(&mut self) {
+        match (self.row, self.scrolling_rows()) {
+            (Some(row), Some((_, bottom))) if row == bottom => self.scroll(1),
+            (Some(row), _) => self.row = Some(self.clamp_row(row.saturating_add(1))),
+            (None, _) => self.forget_contents(),
+        }
+    }
+
+    /// A character has been printed
+    fn print(&mut self, c: char) {
+        let width = c.width().unwrap_or(0) as u16;
+        if width == 0 {
+            // it was combined with the character before, which we can no longer reprint
+            if let (Some(row), Some(column)) = (self.row, self.column) {
+                let column = if self.pending_wrap { column } else { column.saturating_sub(1) };
+                self.set_cell(row, column, Cell::Unknown);
+            }
+            return;
+        }
+        let columns = self.dimensions.map(|dims| dims.columns);
+        let column = match self.column {
+            Some(column) => column,
+            None => {
+                // we can't tell if the line wrapped, or where the character went
+                if self.autowrap {
+                    self.row = None;
+                }
+                self.forget_contents();
+                return;
+            }
+        };
+        let wraps = match columns {
+            // wide characters that don't fit on the line wrap early
+            Some(columns) => self.pending_wrap || column + width > columns,
+            None => false,
+        };
+        let column = if wraps && self.autowrap {
+            self.line_feed();
+            0
+        } else {
+            column
+        };
+        self.pending_wrap = false;
+        match self.row {
+            Some(row) => {
+                if self.insert_mode {
+                    self.insert_cells(row, column, width);
+                }
+                self.set_cell(row, column, Cell::Char(c, self.generation));
+                if width == 2 {
+                    self.set_cell(row, column + 1, Cell::Wide);
+                }
+            }
+            None => self.forget_contents(),
+        }
+        match columns {
+            Some(columns) if column + width >= columns => {
+                // the cursor stays on the last column until the next character
+                self.column = Some(columns - 1);
+                self.pending_wrap = self.autowrap;
+            }
+            _ => self.column = Some(column + width),
+        }
+    }
+
+    /// Interpret the effect of a control character
+    fn control(&mut self, byte: u8) {
+        match byte {
+            b'\r' => {
+                self.column = Some(0);
+                self.pending_wrap = false;
+            }
+            b'\n' | 0x0b | 0x0c => {
+                self.line_feed();
+                if self.newline_returns {
+                    self.column = Some(0);
+                }
+                self.pending_wrap = false;
+            }
+            b'\t' => self.motion(Motion::TabForward(1)),
+            0x08 => {
+                if !self.pending_wrap {
+                    self.column = self.column.map(|col| col.saturating_sub(1));
+                }
+                self.pending_wrap = false;
+            }
+            _ => (),
+        }
+    }
+
+    /// Interpret text written to the terminal
+    pub fn write(&mut self, buf: &[u8]) {
+        let mut bytes = ::std::mem::replace(&mut self.partial, Vec::new());
+        bytes.extend_from_slice(buf);
+        let mut i = 0;
+        while i < bytes.len() {
+            let byte = bytes[i];
+            match self.state {
+                State::Ground => match byte {
+                    0x1b => self.state = State::Escape,
+                    0x00..=0x1f | 0x7f => self.control(byte),
+                    0x20..=0x7e => self.print(byte as char),
+                    _ => {
+                        // the start of a multi-byte utf-8 character
+                        let len = match byte {
+                            0xc0..=0xdf => 2,
+                            0xe0..=0xef => 3,
+                            0xf0..=0xf7 => 4,
+                            _ => 1,
+                        };
+                        if i + len > bytes.len() {
+                            self.partial = bytes[i..].to_vec();
+                            return;
+                        }
+                        let c = ::std::str::from_utf8(&bytes[i..i + len]).ok()
+                            .and_then(|s| s.chars().next())
+                            .unwrap_or('\u{fffd}');
+                        self.print(c);
+                        i += len;
+                        continue;
+                    }
+                },
+                State::Escape => {
+                    self.state = match byte {
+                        b'[' => State::Csi,
+                        b']' | b'P' | b'X' | b'^' | b'_' => State::String,
+                        // save and restore cursor
+                        b'7' => {
+                            self.save();
+                            State::Ground
+                        }
+                        b'8' => {
+                            self.restore();
+                            State::Ground
+                        }
+                        // index, next line and reverse index
+                        b'D' => {
+                            self.line_feed();
+                            State::Ground
+                        }
+                        b'E' => {
+                            self.line_feed();
+                            self.column = Some(0);
+                            State::Ground
+                        }
+                        b'M' => {
+                            self.reverse_index();
+                            State::Ground
+                        }
+                        b'c' => {
+                            self.scroll_region = None;
+                            self.autowrap = true;
+                            self.insert_mode = false;
+                            self.forget();
+                            State::Ground
+                        }
+                        // character set selection etc. take one more byte
+                        0x20..=0x2f => State::Escape,
+                        _ => State::Ground,
+                    };
+                }
+                State::Csi => {
+                    if byte >= 0x40 && byte <= 0x7e {
+                        // anything other than SGR and a few harmless ones may move the cursor
+                        match byte {
+                            b'm' | b'n' | b'c' | b'q' | b't' | b'p' | b'y' => (),
+                            _ => self.forget(),
+                        }
+                        self.state = State::Ground;
+                    }
+                }
+                State::String => match byte {
+                    0x07 => self.state = State::Ground,
+                    0x1b => self.state = State::StringEscape,
+                    _ => (),
+                },
+                State::StringEscape => {
+                    self.state = if byte == b'\\' { State::Ground } else { State::String };
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tracker;
+    use sys::{Edit, Motion};
+    use {Dimensions, Position};
+
+    fn tracker() -> Tracker {
+        let mut tracker = Tracker::new(Some(Dimensions { rows: 5, columns: 10 }));
+        tracker.set_position(Position { row: 0, column: 0 });
+        tracker
+    }
+
+    fn pos(row: u16, column: u16) -> Option<Position> {
+        Some(Position { row, column })
+    }
+
+    #[test]
+    fn text() {
+        let mut t = tracker();
+        t.write(b"hello");
+        assert_eq!(t.position(), pos(0, 5));
+        t.write(b"\rab\tc");
+        assert_eq!(t.position(), pos(0, 9));
+        t.write(b"\x08\x08\n");
+        assert_eq!(t.position(), pos(1, 0));
+        // wide characters, split across writes
+        t.write("\u{4e16}".as_bytes().split_at(1).0);
+        t.write("\u{4e16}".as_bytes().split_at(1).1);
+        assert_eq!(t.position(), pos(1, 2));
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut t = tracker();
+        t.write(b"0123456789");
+        // the cursor waits at the end of the line
+        assert_eq!(t.position(), pos(0, 9));
+        t.write(b"a");
+        assert_eq!(t.position(), pos(1, 1));
+        // scrolling at the bottom of the screen
+        t.write(b"\n\n\n\n\n\n");
+        assert_eq!(t.position(), pos(4, 0));
+    }
+
+    #[test]
+    fn reprint() {
+        let mut t = tracker();
+        t.write("ab\u{4e16}cd".as_bytes());
+        assert_eq!(t.reprint(0, 1, 5), Some("b\u{4e16}c".as_bytes().to_vec()));
+        // half a wide character
+        assert_eq!(t.reprint(0, 0, 3), None);
+        assert_eq!(t.reprint(0, 4, 7), None);
+        t.style_changed();
+        t.write(b"\rX");
+        // written in a different style
+        assert_eq!(t.reprint(0, 0, 2), None);
+        assert_eq!(t.reprint(0, 0, 1), Some(b"X".to_vec()));
+        t.write(b"\r");
+        t.erase_to_end_of_line();
+        assert_eq!(t.reprint(0, 0, 1), None);
+    }
+
+    #[test]
+    fn scroll_region() {
+        let mut t = tracker();
+        t.write(b"top");
+        t.set_scroll_region(Some((1, 3)));
+        t.write(b"\n\nlog\n\n");
+        // newlines at the bottom of the region scroll it, leaving the top row alone
+        assert_eq!(t.position(), pos(3, 0));
+        assert_eq!(t.reprint(0, 0, 3), Some(b"top".to_vec()));
+        assert_eq!(t.reprint(1, 0, 3), Some(b"log".to_vec()));
+        t.set_position(Position { row: 1, column: 0 });
+        t.write(b"\x1bM");
+        assert_eq!(t.position(), pos(1, 0));
+        assert_eq!(t.reprint(2, 0, 3), Some(b"log".to_vec()));
+    }
+
+    #[test]
+    fn motion_in_scroll_region() {
+        let mut t = tracker();
+        t.set_scroll_region(Some((1, 3)));
+        t.set_position(Position { row: 2, column: 4 });
+        // relative motions from inside the region stop at its margins
+        t.motion(Motion::Up(2));
+        assert_eq!(t.position(), pos(1, 4));
+        t.motion(Motion::NextLine(4));
+        assert_eq!(t.position(), pos(3, 0));
+        // from below the region only the top margin stops it, and the other way round
+        t.set_position(Position { row: 4, column: 0 });
+        t.motion(Motion::Down(1));
+        assert_eq!(t.position(), pos(4, 0));
+        t.motion(Motion::PrevLine(4));
+        assert_eq!(t.position(), pos(1, 0));
+        t.set_position(Position { row: 0, column: 0 });
+        t.motion(Motion::Down(4));
+        assert_eq!(t.position(), pos(3, 0));
+        assert!(t.can_reach_row(0, 3) && t.can_reach_row(4, 1));
+        assert!(!t.can_reach_row(2, 0) && !t.can_reach_row(0, 4));
+    }
+
+    #[test]
+    fn modes() {
+        let mut t = tracker();
+        t.set_autowrap(false);
+        t.write(b"0123456789ab");
+        // the last column is overwritten
+        assert_eq!(t.position(), pos(0, 9));
+        assert_eq!(t.reprint(0, 8, 10), Some(b"8b".to_vec()));
+        t.set_autowrap(true);
+        t.set_insert_mode(true);
+        t.write(b"\rX");
+        assert_eq!(t.position(), pos(0, 1));
+        // nothing can be reprinted in insert mode
+        assert_eq!(t.reprint(0, 0, 1), None);
+        t.set_insert_mode(false);
+        // the rest of the line was pushed along, and the last character lost
+        assert_eq!(t.reprint(0, 0, 10), Some(b"X012345678".to_vec()));
+        // in raw mode a newline only moves down
+        t.set_newline_returns(false);
+        t.write(b"\n");
+        assert_eq!(t.position(), pos(1, 1));
+    }
+
+    #[test]
+    fn edit() {
+        let mut t = tracker();
+        t.write(b"abcdef\r\nline");
+        t.set_position(Position { row: 0, column: 1 });
+        t.edit(Edit::DeleteChars(2));
+        assert_eq!(t.reprint(0, 0, 4), Some(b"adef".to_vec()));
+        t.edit(Edit::InsertChars(1));
+        assert_eq!(t.reprint(0, 2, 5), Some(b"def".to_vec()));
+        assert_eq!(t.reprint(0, 1, 2), None);
+        t.edit(Edit::InsertLines(1));
+        assert_eq!(t.position(), pos(0, 0));
+        assert_eq!(t.reprint(2, 0, 4), Some(b"line".to_vec()));
+    }
+
+    #[test]
+    fn escapes() {
+        let mut t = tracker();
+        t.write(b"\x1b[1;31mab\x1b]8;;http://example.com\x1b\\cd\x1b]0;title\x07");
+        assert_eq!(t.position(), pos(0, 4));
+        t.write(b"\x1b[2;3H");
+        assert_eq!(t.position(), None);
+    }
+}